@@ -0,0 +1,496 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
+
+/// Arbitrary-precision signed integer, stored as a sign and a little-endian
+/// magnitude of base 2^32 limbs with no trailing zero limbs. Zero is never
+/// negative, so the derived equality is numeric equality.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BigInt {
+    negative: bool,
+    magnitude: Vec<u32>,
+}
+
+const DECIMAL_CHUNK: u32 = 1_000_000_000;
+const DECIMAL_CHUNK_DIGITS: usize = 9;
+
+fn trim(mut mag: Vec<u32>) -> Vec<u32> {
+    while mag.last() == Some(&0) {
+        mag.pop();
+    }
+    mag
+}
+
+fn cmp_magnitude(a: &[u32], b: &[u32]) -> Ordering {
+    if a.len() != b.len() {
+        return a.len().cmp(&b.len());
+    }
+    for (x, y) in a.iter().rev().zip(b.iter().rev()) {
+        if x != y {
+            return x.cmp(y);
+        }
+    }
+    Ordering::Equal
+}
+
+fn add_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0u64;
+    for i in 0..a.len().max(b.len()) {
+        let sum = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        out.push(sum as u32);
+        carry = sum >> 32;
+    }
+    if carry > 0 {
+        out.push(carry as u32);
+    }
+    out
+}
+
+/// Requires `a >= b`.
+fn sub_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, x) in a.iter().enumerate() {
+        let mut diff = *x as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        if diff < 0 {
+            diff += 1 << 32;
+            borrow = 1;
+        } else {
+            borrow = 0;
+        }
+        out.push(diff as u32);
+    }
+    trim(out)
+}
+
+fn mul_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = vec![0u32; a.len() + b.len()];
+    for (i, x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, y) in b.iter().enumerate() {
+            let cur = out[i + j] as u64 + (*x as u64) * (*y as u64) + carry;
+            out[i + j] = cur as u32;
+            carry = cur >> 32;
+        }
+        out[i + b.len()] = carry as u32;
+    }
+    trim(out)
+}
+
+/// Multiplies in place by `m` and adds `a`.
+fn mul_add_small(mag: &mut Vec<u32>, m: u32, a: u32) {
+    let mut carry = a as u64;
+    for limb in mag.iter_mut() {
+        let cur = (*limb as u64) * (m as u64) + carry;
+        *limb = cur as u32;
+        carry = cur >> 32;
+    }
+    if carry > 0 {
+        mag.push(carry as u32);
+    }
+}
+
+/// Divides in place by `d` and returns the remainder.
+fn div_rem_small(mag: &mut Vec<u32>, d: u32) -> u32 {
+    let mut rem = 0u64;
+    for limb in mag.iter_mut().rev() {
+        let cur = (rem << 32) | *limb as u64;
+        *limb = (cur / d as u64) as u32;
+        rem = cur % d as u64;
+    }
+    let trimmed = trim(std::mem::take(mag));
+    *mag = trimmed;
+    rem as u32
+}
+
+fn shl1(mag: &mut Vec<u32>) {
+    let mut carry = 0;
+    for limb in mag.iter_mut() {
+        let next = *limb >> 31;
+        *limb = (*limb << 1) | carry;
+        carry = next;
+    }
+    if carry > 0 {
+        mag.push(carry);
+    }
+}
+
+/// Binary long division of magnitudes. Requires a non-empty divisor.
+fn div_rem_magnitude(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if cmp_magnitude(a, b) == Ordering::Less {
+        return (Vec::new(), a.to_vec());
+    }
+    let mut quotient = vec![0u32; a.len()];
+    let mut rem: Vec<u32> = Vec::new();
+    for bit in (0..a.len() * 32).rev() {
+        shl1(&mut rem);
+        if (a[bit / 32] >> (bit % 32)) & 1 == 1 {
+            if rem.is_empty() {
+                rem.push(1);
+            } else {
+                rem[0] |= 1;
+            }
+        }
+        if cmp_magnitude(&rem, b) != Ordering::Less {
+            rem = sub_magnitude(&rem, b);
+            quotient[bit / 32] |= 1 << (bit % 32);
+        }
+    }
+    (trim(quotient), rem)
+}
+
+impl BigInt {
+    fn from_parts(negative: bool, magnitude: Vec<u32>) -> BigInt {
+        let magnitude = trim(magnitude);
+        BigInt {
+            negative: negative && !magnitude.is_empty(),
+            magnitude,
+        }
+    }
+
+    pub fn from_i64(v: i64) -> BigInt {
+        let m = v.unsigned_abs();
+        BigInt::from_parts(v < 0, vec![m as u32, (m >> 32) as u32])
+    }
+
+    pub fn from_u64(m: u64) -> BigInt {
+        BigInt::from_parts(false, vec![m as u32, (m >> 32) as u32])
+    }
+
+    /// Converts an integral float exactly. `None` for NaN, infinities and
+    /// values with a fractional part.
+    pub fn from_f64(f: f64) -> Option<BigInt> {
+        if !f.is_finite() || f.fract() != 0.0 {
+            return None;
+        }
+        let bits = f.to_bits();
+        let exponent = ((bits >> 52) & 0x7ff) as i64;
+        let mut mantissa = bits & ((1 << 52) - 1);
+        if exponent != 0 {
+            mantissa |= 1 << 52;
+        }
+        // The value is mantissa * 2^shift; an integral value with a negative
+        // shift only loses zero bits.
+        let shift = exponent - 1075;
+        let magnitude = if shift < 0 {
+            // Zero and subnormals shift past every bit.
+            BigInt::from_i64(mantissa.checked_shr(-shift as u32).unwrap_or(0) as i64)
+        } else {
+            &BigInt::from_i64(mantissa as i64) * &BigInt::from_i64(2).pow(shift as u32)
+        };
+        if f < 0.0 {
+            Some(-&magnitude)
+        } else {
+            Some(magnitude)
+        }
+    }
+
+    /// Parses an optionally signed string of decimal digits.
+    pub fn parse(s: &str) -> Option<BigInt> {
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let mut magnitude = Vec::new();
+        let first = digits.len() % DECIMAL_CHUNK_DIGITS;
+        let mut chunks = vec![&digits[..first]];
+        chunks.extend(
+            digits.as_bytes()[first..]
+                .chunks(DECIMAL_CHUNK_DIGITS)
+                .map(|c| std::str::from_utf8(c).unwrap()),
+        );
+        for chunk in chunks.into_iter().filter(|c| !c.is_empty()) {
+            mul_add_small(&mut magnitude, DECIMAL_CHUNK, chunk.parse().unwrap());
+        }
+        Some(BigInt::from_parts(negative, magnitude))
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.magnitude.is_empty()
+    }
+
+    pub fn is_odd(&self) -> bool {
+        self.magnitude.first().is_some_and(|l| l & 1 == 1)
+    }
+
+    /// The number of bits in the magnitude. Zero has none.
+    pub fn bits(&self) -> u64 {
+        match self.magnitude.last() {
+            Some(top) => self.magnitude.len() as u64 * 32 - top.leading_zeros() as u64,
+            None => 0,
+        }
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.magnitude.len() > 2 {
+            return None;
+        }
+        let m = self
+            .magnitude
+            .iter()
+            .rev()
+            .fold(0u64, |acc, l| (acc << 32) | *l as u64);
+        if self.negative {
+            0i64.checked_sub_unsigned(m)
+        } else {
+            i64::try_from(m).ok()
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let m = self
+            .magnitude
+            .iter()
+            .rev()
+            .fold(0.0, |acc, l| acc * 4294967296.0 + *l as f64);
+        if self.negative {
+            -m
+        } else {
+            m
+        }
+    }
+
+    /// Truncating division, so the remainder takes the sign of the
+    /// dividend like `%` on the other number types. `None` when dividing
+    /// by zero.
+    pub fn div_rem(&self, other: &BigInt) -> Option<(BigInt, BigInt)> {
+        if other.is_zero() {
+            return None;
+        }
+        let (q, r) = div_rem_magnitude(&self.magnitude, &other.magnitude);
+        Some((
+            BigInt::from_parts(self.negative != other.negative, q),
+            BigInt::from_parts(self.negative, r),
+        ))
+    }
+
+    pub fn pow(&self, mut exp: u32) -> BigInt {
+        let mut base = self.clone();
+        let mut acc = BigInt::from_i64(1);
+        while exp > 0 {
+            if exp & 1 == 1 {
+                acc = &acc * &base;
+            }
+            exp >>= 1;
+            if exp > 0 {
+                base = &base * &base;
+            }
+        }
+        acc
+    }
+}
+
+impl Neg for &BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        BigInt::from_parts(!self.negative, self.magnitude.clone())
+    }
+}
+
+impl Add for &BigInt {
+    type Output = BigInt;
+
+    fn add(self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::from_parts(
+                self.negative,
+                add_magnitude(&self.magnitude, &other.magnitude),
+            );
+        }
+        match cmp_magnitude(&self.magnitude, &other.magnitude) {
+            Ordering::Less => BigInt::from_parts(
+                other.negative,
+                sub_magnitude(&other.magnitude, &self.magnitude),
+            ),
+            _ => BigInt::from_parts(
+                self.negative,
+                sub_magnitude(&self.magnitude, &other.magnitude),
+            ),
+        }
+    }
+}
+
+impl Sub for &BigInt {
+    type Output = BigInt;
+
+    fn sub(self, other: &BigInt) -> BigInt {
+        self + &(-other)
+    }
+}
+
+impl Mul for &BigInt {
+    type Output = BigInt;
+
+    fn mul(self, other: &BigInt) -> BigInt {
+        BigInt::from_parts(
+            self.negative != other.negative,
+            mul_magnitude(&self.magnitude, &other.magnitude),
+        )
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &BigInt) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &BigInt) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_magnitude(&self.magnitude, &other.magnitude),
+            (true, true) => cmp_magnitude(&other.magnitude, &self.magnitude),
+        }
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        let mut mag = self.magnitude.clone();
+        let mut chunks = Vec::new();
+        while !mag.is_empty() {
+            chunks.push(div_rem_small(&mut mag, DECIMAL_CHUNK));
+        }
+        if self.negative {
+            write!(f, "-")?;
+        }
+        let mut chunks = chunks.iter().rev();
+        if let Some(first) = chunks.next() {
+            write!(f, "{}", first)?;
+        }
+        for c in chunks {
+            write!(f, "{:09}", c)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(s: &str) -> BigInt {
+        BigInt::parse(s).unwrap()
+    }
+
+    #[test]
+    fn div_rem_truncates_toward_zero() {
+        let cases = [
+            ("7", "2", "3", "1"),
+            ("-7", "2", "-3", "-1"),
+            ("7", "-2", "-3", "1"),
+            ("-7", "-2", "3", "-1"),
+            ("-6", "3", "-2", "0"),
+        ];
+        for (a, b, q, r) in cases {
+            let (quotient, remainder) = big(a).div_rem(&big(b)).unwrap();
+            assert_eq!((quotient, remainder), (big(q), big(r)), "{} / {}", a, b);
+        }
+        let (q, r) = big("-100000000000000000000000")
+            .div_rem(&big("30000000000"))
+            .unwrap();
+        assert_eq!(q, big("-3333333333333"));
+        assert_eq!(r, big("-10000000000"));
+        assert!(big("1").div_rem(&big("0")).is_none());
+    }
+
+    #[test]
+    fn remainder_of_zero_is_not_negative() {
+        let (_, r) = big("-6").div_rem(&big("3")).unwrap();
+        assert!(!r.negative);
+        assert_eq!(r.to_string(), "0");
+    }
+
+    #[test]
+    fn parse_and_display_round_trip() {
+        for s in [
+            "0",
+            "1",
+            "-1",
+            "999999999",
+            "1000000000",
+            "-1000000000",
+            "1000000001",
+            "999999999999999999",
+            "1000000000000000000",
+            "123456789012345678901234567890",
+            "-100000000000000000000000000000000000",
+        ] {
+            assert_eq!(big(s).to_string(), s);
+        }
+        assert_eq!(big("000123").to_string(), "123");
+        assert_eq!(big("-0").to_string(), "0");
+        assert!(BigInt::parse("").is_none());
+        assert!(BigInt::parse("-").is_none());
+        assert!(BigInt::parse("12a").is_none());
+    }
+
+    #[test]
+    fn from_f64_is_exact() {
+        assert_eq!(
+            BigInt::from_f64(9223372036854775808.0),
+            Some(big("9223372036854775808"))
+        );
+        assert_eq!(
+            BigInt::from_f64(18446744073709551616.0),
+            Some(big("18446744073709551616"))
+        );
+        assert_eq!(
+            BigInt::from_f64(-18446744073709551616.0),
+            Some(big("-18446744073709551616"))
+        );
+        assert_eq!(BigInt::from_f64(0.0), Some(big("0")));
+        assert_eq!(BigInt::from_f64(-3.0), Some(big("-3")));
+        assert_eq!(BigInt::from_f64(0.5), None);
+        assert_eq!(BigInt::from_f64(f64::INFINITY), None);
+        assert_eq!(BigInt::from_f64(f64::NAN), None);
+    }
+
+    #[test]
+    fn to_i64_at_the_limits() {
+        assert_eq!(BigInt::from_i64(i64::MAX).to_i64(), Some(i64::MAX));
+        assert_eq!(BigInt::from_i64(i64::MIN).to_i64(), Some(i64::MIN));
+        assert_eq!(big("9223372036854775808").to_i64(), None);
+        assert_eq!(big("-9223372036854775809").to_i64(), None);
+        assert_eq!(big("18446744073709551616").to_i64(), None);
+    }
+
+    #[test]
+    fn bits_counts_the_magnitude() {
+        assert_eq!(big("0").bits(), 0);
+        assert_eq!(big("1").bits(), 1);
+        assert_eq!(big("-8").bits(), 4);
+        assert_eq!(big("4294967295").bits(), 32);
+        assert_eq!(big("4294967296").bits(), 33);
+    }
+
+    #[test]
+    fn add_and_sub_carry_across_limbs() {
+        assert_eq!(&big("4294967295") + &big("1"), big("4294967296"));
+        assert_eq!(
+            &big("18446744073709551615") + &big("1"),
+            big("18446744073709551616")
+        );
+        assert_eq!(&big("4294967296") - &big("1"), big("4294967295"));
+        assert_eq!(
+            &big("18446744073709551616") - &big("1"),
+            big("18446744073709551615")
+        );
+        assert_eq!(
+            &big("1") - &big("18446744073709551616"),
+            big("-18446744073709551615")
+        );
+        assert_eq!(&big("-4294967296") + &big("4294967296"), big("0"));
+        assert_eq!(&big("-1") - &big("4294967295"), big("-4294967296"));
+    }
+}
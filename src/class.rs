@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::interpreter::Function;
+use crate::parser::{LiteralType, MethodKind};
+
+/// The members of one level of a class, by name. A name can have a getter
+/// and a setter alongside each other, but only one method.
+#[derive(Default)]
+pub struct Methods<'src> {
+    methods: HashMap<&'src str, Rc<Function<'src>>>,
+    getters: HashMap<&'src str, Rc<Function<'src>>>,
+    setters: HashMap<&'src str, Rc<Function<'src>>>,
+}
+
+impl<'src> Methods<'src> {
+    pub fn insert(&mut self, kind: MethodKind, name: &'src str, function: Rc<Function<'src>>) {
+        let members = match kind {
+            MethodKind::Method => &mut self.methods,
+            MethodKind::Getter => &mut self.getters,
+            MethodKind::Setter => &mut self.setters,
+        };
+        members.insert(name, function);
+    }
+
+    pub fn get(&self, kind: MethodKind, name: &str) -> Option<Rc<Function<'src>>> {
+        let members = match kind {
+            MethodKind::Method => &self.methods,
+            MethodKind::Getter => &self.getters,
+            MethodKind::Setter => &self.setters,
+        };
+        members.get(name).cloned()
+    }
+}
+
+/// A class value. Its instances look members up in `methods`, and the
+/// class itself looks them up in its metaclass, which holds the members
+/// declared with a `class` prefix. A metaclass has no metaclass of its own.
+pub struct Class<'src> {
+    pub name: &'src str,
+    pub methods: Methods<'src>,
+    pub metaclass: Option<Rc<Class<'src>>>,
+}
+
+impl fmt::Debug for Class<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<class {}>", self.name)
+    }
+}
+
+/// An instance of a class, holding the fields assigned to it so far.
+#[derive(Debug)]
+pub struct Instance<'src> {
+    pub class: Rc<Class<'src>>,
+    pub fields: HashMap<&'src str, LiteralType<'src>>,
+}
+
+impl<'src> Instance<'src> {
+    pub fn new(class: Rc<Class<'src>>) -> Instance<'src> {
+        Instance {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+}
+
+/// A member of a class or trait, before it is put in a `Methods`.
+#[derive(Clone)]
+pub struct Member<'src> {
+    pub name: &'src str,
+    pub kind: MethodKind,
+    pub class_level: bool,
+    pub function: Rc<Function<'src>>,
+}
+
+/// A trait value: members that classes declared `with` it take on.
+pub struct Trait<'src> {
+    pub name: &'src str,
+    pub members: Vec<Member<'src>>,
+}
+
+impl fmt::Debug for Trait<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<trait {}>", self.name)
+    }
+}
@@ -0,0 +1,92 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::interpreter::InterpreterError;
+use crate::parser::LiteralType;
+use crate::token::Token;
+
+#[derive(Debug, Default)]
+pub struct Environment<'src> {
+    values: HashMap<&'src str, LiteralType<'src>>,
+    // Names in `values` that were declared with `const`.
+    constants: HashSet<&'src str>,
+    enclosing: Option<Rc<RefCell<Environment<'src>>>>,
+}
+
+impl<'src> Environment<'src> {
+    pub fn new() -> Environment<'src> {
+        Environment::default()
+    }
+
+    pub fn new_enclosed(enclosing: Rc<RefCell<Environment<'src>>>) -> Environment<'src> {
+        Environment {
+            values: HashMap::new(),
+            constants: HashSet::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    /// The names defined in this scope itself and their values, in name
+    /// order.
+    pub fn values(&self) -> Vec<(&'src str, LiteralType<'src>)> {
+        let mut values: Vec<_> = self
+            .values
+            .iter()
+            .map(|(name, value)| (*name, value.clone()))
+            .collect();
+        values.sort_by(|a, b| a.0.cmp(b.0));
+        values
+    }
+
+    pub fn define(&mut self, name: &'src str, value: LiteralType<'src>) {
+        self.constants.remove(name);
+        self.values.insert(name, value);
+    }
+
+    pub fn define_const(&mut self, name: &'src str, value: LiteralType<'src>) {
+        self.values.insert(name, value);
+        self.constants.insert(name);
+    }
+
+    pub fn get(&self, name: &Token<'src>) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+        if let Some(v) = self.values.get(name.lexeme) {
+            return Ok(v.clone());
+        }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().get(name),
+            None => Err(undefined_variable(name)),
+        }
+    }
+
+    pub fn assign(
+        &mut self,
+        name: &Token<'src>,
+        value: LiteralType<'src>,
+    ) -> Result<(), InterpreterError<'src>> {
+        if let Some(v) = self.values.get_mut(name.lexeme) {
+            // The resolver rejects these statically; this catches the rest.
+            if self.constants.contains(name.lexeme) {
+                return Err(InterpreterError {
+                    tok: name.clone(),
+                    msg: format!("Can't assign to constant '{}'.", name.lexeme),
+                    thrown: None,
+                });
+            }
+            *v = value;
+            return Ok(());
+        }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+            None => Err(undefined_variable(name)),
+        }
+    }
+}
+
+fn undefined_variable<'src>(name: &Token<'src>) -> InterpreterError<'src> {
+    InterpreterError {
+        tok: name.clone(),
+        msg: format!("Undefined variable '{}'.", name.lexeme),
+        thrown: None,
+    }
+}
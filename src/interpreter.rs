@@ -1,227 +1,2506 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::{error::Error, fmt};
 
-use crate::parser::{Expression, LiteralType};
-use crate::token::{Token, TokenType};
+use crate::bigint::BigInt;
+use crate::class::{Class, Instance, Member, Methods, Trait};
+use crate::environment::Environment;
+use crate::list::List;
+use crate::map::{Map, MapKey};
+use crate::modules;
+use crate::natives::{define_natives, list_index, NativeFunction};
+use crate::parser::{
+    Expression, Lambda, LiteralType, MatchArm, Method, MethodKind, Parser, Pattern, Statement,
+    Target,
+};
+use crate::range::Range;
+use crate::resolver::Resolver;
+use crate::scanner::new_scanner;
+use crate::token::{Literal, Token, TokenType};
 
 #[derive(Debug)]
-pub struct InterpreterError {
-    pub tok: Token,
+pub struct InterpreterError<'src> {
+    pub tok: Token<'src>,
     pub msg: String,
+    /// The value passed to `throw`, or `None` for an error raised by the
+    /// interpreter itself.
+    pub thrown: Option<Box<LiteralType<'src>>>,
 }
 
-impl Error for InterpreterError {}
+impl Error for InterpreterError<'_> {}
 
-impl fmt::Display for InterpreterError {
+impl fmt::Display for InterpreterError<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", self.msg)
     }
 }
 
+/// A function value: the lambda it was created from and the environment
+/// that was current at that point, which its body can keep referring to.
+/// `file` is the script or module it was written in, which imports in its
+/// body are resolved against.
+pub struct Function<'src> {
+    declaration: Rc<Lambda<'src>>,
+    closure: Rc<RefCell<Environment<'src>>>,
+    file: Option<Rc<PathBuf>>,
+}
+
+impl fmt::Debug for Function<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<fn>")
+    }
+}
+
+impl<'src> Function<'src> {
+    /// A copy of this function whose body sees `receiver` as `this`.
+    fn bind(&self, receiver: LiteralType<'src>) -> Rc<Function<'src>> {
+        let mut environment = Environment::new_enclosed(Rc::clone(&self.closure));
+        environment.define("this", receiver);
+        Rc::new(Function {
+            declaration: Rc::clone(&self.declaration),
+            closure: Rc::new(RefCell::new(environment)),
+            file: self.file.clone(),
+        })
+    }
+}
+
+/// The class whose methods apply to `object`: an instance's class, or a
+/// class's metaclass.
+fn receiver_class<'src>(
+    object: &LiteralType<'src>,
+    name: &Token<'src>,
+) -> Result<Rc<Class<'src>>, InterpreterError<'src>> {
+    let class = match object {
+        LiteralType::Instance { lit } => Some(Rc::clone(&lit.borrow().class)),
+        LiteralType::Class { lit } => lit.metaclass.clone(),
+        _ => None,
+    };
+    class.ok_or_else(|| InterpreterError {
+        tok: name.clone(),
+        msg: "Only instances and classes have properties.".to_string(),
+        thrown: None,
+    })
+}
+
+/// The error for a class that doesn't declare `member` although two of its
+/// traits provide it.
+pub fn trait_conflict(class: &str, member: &str, first: &str, second: &str) -> String {
+    format!(
+        "Class '{}' must declare '{}', which traits '{}' and '{}' both provide.",
+        class, member, first, second
+    )
+}
+
 fn is_truthy(lit: &LiteralType) -> bool {
     match lit {
-        LiteralType::Nil { lit } => false,
-        LiteralType::Bool { lit } => lit.clone(),
-        LiteralType::Float { lit } => true,
-        LiteralType::String { lit } => true,
+        LiteralType::Nil => false,
+        LiteralType::Bool { lit } => *lit,
+        LiteralType::Integer { .. } => true,
+        LiteralType::BigInt { .. } => true,
+        LiteralType::Float { .. } => true,
+        LiteralType::String { .. } => true,
+        LiteralType::Function { .. } => true,
+        LiteralType::Native { .. } => true,
+        LiteralType::List { .. } => true,
+        LiteralType::Map { .. } => true,
+        LiteralType::Range { .. } => true,
+        LiteralType::Class { .. } => true,
+        LiteralType::Trait { .. } => true,
+        LiteralType::Instance { .. } => true,
     }
 }
 
-fn is_equal(a: LiteralType, b: LiteralType) -> bool {
+fn is_equal<'src>(a: LiteralType<'src>, b: LiteralType<'src>) -> bool {
     match a {
-        LiteralType::Nil { lit } => match b {
-            LiteralType::Nil { lit } => true,
-            _ => false,
-        },
+        LiteralType::Nil => matches!(b, LiteralType::Nil),
         LiteralType::Bool { lit: al } => match b {
-            LiteralType::Bool { lit: bl } => (al == bl),
+            LiteralType::Bool { lit: bl } => al == bl,
             _ => false,
         },
-        LiteralType::Float { lit: al } => match b {
-            LiteralType::Float { lit: bl } => (al == bl),
-            _ => false, // maybe panic?
-        },
+        LiteralType::Integer { .. } | LiteralType::BigInt { .. } | LiteralType::Float { .. } => {
+            compare_numbers(&a, &b) == Some(Ordering::Equal)
+        }
         LiteralType::String { lit: al } => match b {
-            LiteralType::String { lit: bl } => (al == bl),
+            LiteralType::String { lit: bl } => al == bl,
             _ => false, // maybe panic?
         },
+        LiteralType::Function { lit: al } => match b {
+            LiteralType::Function { lit: bl } => Rc::ptr_eq(&al, &bl),
+            _ => false,
+        },
+        LiteralType::Native { lit: al } => match b {
+            LiteralType::Native { lit: bl } => al.name == bl.name,
+            _ => false,
+        },
+        // Lists are reference values: equal only when they are the same list.
+        LiteralType::List { lit: al } => match b {
+            LiteralType::List { lit: bl } => Rc::ptr_eq(&al, &bl),
+            _ => false,
+        },
+        LiteralType::Map { lit: al } => match b {
+            LiteralType::Map { lit: bl } => Rc::ptr_eq(&al, &bl),
+            _ => false,
+        },
+        LiteralType::Range { lit: al } => match b {
+            LiteralType::Range { lit: bl } => al == bl,
+            _ => false,
+        },
+        LiteralType::Class { lit: al } => match b {
+            LiteralType::Class { lit: bl } => Rc::ptr_eq(&al, &bl),
+            _ => false,
+        },
+        LiteralType::Trait { lit: al } => match b {
+            LiteralType::Trait { lit: bl } => Rc::ptr_eq(&al, &bl),
+            _ => false,
+        },
+        LiteralType::Instance { lit: al } => match b {
+            LiteralType::Instance { lit: bl } => Rc::ptr_eq(&al, &bl),
+            _ => false,
+        },
+    }
+}
+
+/// Formats a value the way the REPL prints it. Strings are quoted; a list
+/// or map that contains itself prints the inner occurrence as `[...]` or
+/// `{...}`.
+pub fn stringify(lit: &LiteralType) -> String {
+    let mut seen = Vec::new();
+    stringify_nested(lit, &mut seen, &mut |_| None)
+}
+
+/// `show_instance` can give the text for an instance, which otherwise
+/// prints as `<Name instance>`.
+fn stringify_nested<'src>(
+    lit: &LiteralType<'src>,
+    seen: &mut Vec<*const ()>,
+    show_instance: &mut dyn FnMut(&LiteralType<'src>) -> Option<String>,
+) -> String {
+    match lit {
+        LiteralType::Integer { lit } => format!("{}", lit),
+        LiteralType::BigInt { lit } => format!("{}", lit),
+        // Debug formatting keeps the fractional part, so the float 3.0
+        // prints differently from the integer 3.
+        LiteralType::Float { lit } => format!("{:?}", lit),
+        LiteralType::String { lit } => format!("\"{}\"", lit),
+        LiteralType::Bool { lit } => format!("{}", lit),
+        LiteralType::Function { .. } => "<fn>".to_string(),
+        LiteralType::Native { lit } => format!("{:?}", lit),
+        LiteralType::Range { lit } => format!("range({}, {}, {})", lit.start, lit.end, lit.step),
+        LiteralType::Class { lit } => format!("<class {}>", lit.name),
+        LiteralType::Trait { lit } => format!("<trait {}>", lit.name),
+        LiteralType::Instance { lit: instance } => show_instance(lit)
+            .unwrap_or_else(|| format!("<{} instance>", instance.borrow().class.name)),
+        LiteralType::List { lit } => {
+            let ptr = Rc::as_ptr(lit) as *const ();
+            if seen.contains(&ptr) {
+                return "[...]".to_string();
+            }
+            seen.push(ptr);
+            let elements: Vec<String> = lit
+                .borrow()
+                .iter()
+                .map(|e| stringify_nested(e, seen, show_instance))
+                .collect();
+            seen.pop();
+            format!("[{}]", elements.join(", "))
+        }
+        LiteralType::Map { lit } => {
+            let ptr = Rc::as_ptr(lit) as *const ();
+            if seen.contains(&ptr) {
+                return "{...}".to_string();
+            }
+            seen.push(ptr);
+            let entries: Vec<String> = lit
+                .borrow()
+                .entries()
+                .iter()
+                .map(|(k, v)| {
+                    format!(
+                        "{}: {}",
+                        stringify_nested(k, seen, show_instance),
+                        stringify_nested(v, seen, show_instance)
+                    )
+                })
+                .collect();
+            seen.pop();
+            format!("{{{}}}", entries.join(", "))
+        }
+        LiteralType::Nil => "nil".to_string(),
     }
 }
 
-fn check_number_operands(
-    left: &LiteralType,
-    right: &LiteralType,
-    operator: &Token,
-) -> Result<bool, InterpreterError> {
-    let mut rv = true;
-    match left {
-        LiteralType::Bool { lit } => rv &= false,
-        LiteralType::String { lit } => rv &= false,
-        LiteralType::Nil { lit } => rv &= false,
-        LiteralType::Float { lit } => rv &= true,
+fn literal_value<'src>(lit: &Literal) -> LiteralType<'src> {
+    match lit {
+        Literal::Bool { lit } => LiteralType::Bool { lit: *lit },
+        Literal::Integer { lit } => LiteralType::Integer { lit: *lit },
+        Literal::BigInt { lit } => LiteralType::BigInt { lit: lit.clone() },
+        Literal::Float { lit } => LiteralType::Float { lit: *lit },
+        Literal::String { lit } => LiteralType::String { lit: lit.clone() },
+        Literal::Nil => LiteralType::Nil,
     }
-    match right {
-        LiteralType::Bool { lit } => rv &= false,
-        LiteralType::String { lit } => rv &= false,
-        LiteralType::Nil { lit } => rv &= false,
-        LiteralType::Float { lit } => rv &= true,
+}
+
+/// Whether literal patterns `a` and `b` match the same values. They compare
+/// with `==` as in `match_pattern`, so `1` and `1.0` are the same pattern.
+pub fn same_literal(a: &Literal, b: &Literal) -> bool {
+    is_equal(literal_value(a), literal_value(b))
+}
+
+/// Tests `value` against `pattern`, collecting the names it binds. Literal
+/// patterns compare with `==`.
+fn match_pattern<'src>(
+    pattern: &Pattern<'src>,
+    value: &LiteralType<'src>,
+    bindings: &mut Vec<(&'src str, LiteralType<'src>)>,
+) -> bool {
+    match pattern {
+        Pattern::Wildcard => true,
+        Pattern::Literal { lit } => is_equal(literal_value(lit), value.clone()),
+        Pattern::Binding { name } => {
+            bindings.push((name.lexeme, value.clone()));
+            true
+        }
+        Pattern::List { elements } => match value {
+            LiteralType::List { lit } => {
+                let list = lit.borrow();
+                list.len() == elements.len()
+                    && elements
+                        .iter()
+                        .zip(list.iter())
+                        .all(|(p, v)| match_pattern(p, v, bindings))
+            }
+            _ => false,
+        },
+        Pattern::Alternatives { alternatives } => alternatives
+            .iter()
+            .any(|p| match_pattern(p, value, bindings)),
     }
+}
+
+/// Describes the number of arguments a callable accepts: exactly `min`, a
+/// range up to `max`, or at least `min` when `max` is `None`.
+fn arity_error<'src>(
+    paren: &Token<'src>,
+    min: usize,
+    max: Option<usize>,
+    got: usize,
+) -> InterpreterError<'src> {
+    let expected = match max {
+        Some(max) if max == min => format!("{}", min),
+        Some(max) => format!("{} to {}", min, max),
+        None => format!("at least {}", min),
+    };
+    InterpreterError {
+        tok: paren.clone(),
+        msg: format!("Expected {} arguments but got {}.", expected, got),
+        thrown: None,
+    }
+}
+
+/// The value a catch clause binds: whatever was thrown, or for an error
+/// raised by the interpreter a map holding its message and line.
+fn error_value<'src>(error: &InterpreterError<'src>) -> LiteralType<'src> {
+    if let Some(value) = &error.thrown {
+        return (**value).clone();
+    }
+    let mut map = Map::new();
+    for (key, value) in [
+        (
+            "message",
+            LiteralType::String {
+                lit: error.msg.clone(),
+            },
+        ),
+        (
+            "line",
+            LiteralType::Integer {
+                lit: error.tok.line,
+            },
+        ),
+    ] {
+        map.insert(
+            MapKey::String(key.to_string()),
+            LiteralType::String {
+                lit: key.to_string(),
+            },
+            value,
+        );
+    }
+    LiteralType::Map {
+        lit: Rc::new(RefCell::new(map)),
+    }
+}
+
+/// Maps a compound assignment operator such as `+=` to its binary operator,
+/// or `None` for plain `=`.
+fn compound_operator(operator: &Token) -> Option<TokenType> {
+    match operator.tok_type {
+        TokenType::PlusEqual => Some(TokenType::Plus),
+        TokenType::MinusEqual => Some(TokenType::Minus),
+        TokenType::StarEqual => Some(TokenType::Star),
+        TokenType::SlashEqual => Some(TokenType::Slash),
+        TokenType::PercentEqual => Some(TokenType::Percent),
+        _ => None,
+    }
+}
+
+fn expect_list<'src>(
+    value: LiteralType<'src>,
+    bracket: &Token<'src>,
+    msg: &str,
+) -> Result<Rc<RefCell<List<'src>>>, InterpreterError<'src>> {
+    match value {
+        LiteralType::List { lit } => Ok(lit),
+        _ => Err(InterpreterError {
+            tok: bracket.clone(),
+            msg: msg.to_string(),
+            thrown: None,
+        }),
+    }
+}
+
+fn resolve_list_index<'src>(
+    index: &LiteralType<'src>,
+    bracket: &Token<'src>,
+    len: usize,
+) -> Result<usize, InterpreterError<'src>> {
+    let index = to_integer(index, bracket, "List index must be an integer.")?;
+    match list_index(index, len) {
+        Some(i) => Ok(i),
+        None => Err(InterpreterError {
+            tok: bracket.clone(),
+            msg: format!("List index {} out of range for length {}.", index, len),
+            thrown: None,
+        }),
+    }
+}
+
+pub fn missing_key<'src>(tok: &Token<'src>, key: &LiteralType<'src>) -> InterpreterError<'src> {
+    InterpreterError {
+        tok: tok.clone(),
+        msg: format!("Undefined key {}.", stringify(key)),
+        thrown: None,
+    }
+}
+
+/// Resolves a slice bound: negative values count back from the end, and
+/// anything outside the list is clamped to it.
+fn slice_bound(bound: i64, len: usize) -> usize {
+    let len = len as i64;
+    let b = if bound < 0 { bound + len } else { bound };
+    b.clamp(0, len) as usize
+}
+
+fn is_number(lit: &LiteralType) -> bool {
+    matches!(
+        lit,
+        LiteralType::Integer { .. } | LiteralType::BigInt { .. } | LiteralType::Float { .. }
+    )
+}
+
+/// Orders an integer against a float by exact value, without rounding the
+/// integer to the nearest representable float first.
+fn compare_integer_float(i: i64, f: f64) -> Option<Ordering> {
+    if f.is_nan() {
+        return None;
+    }
+    if f >= -(i64::MIN as f64) {
+        return Some(Ordering::Less);
+    }
+    if f < i64::MIN as f64 {
+        return Some(Ordering::Greater);
+    }
+    let whole = f.trunc();
+    match i.cmp(&(whole as i64)) {
+        Ordering::Equal => 0.0.partial_cmp(&(f - whole)),
+        o => Some(o),
+    }
+}
+
+/// Compares exactly against the float's integral part, so a big integer
+/// equals a float exactly when they share a map key.
+fn compare_big_float(b: &BigInt, f: f64) -> Option<Ordering> {
+    if f.is_nan() {
+        return None;
+    }
+    if f.is_infinite() {
+        return Some(if f > 0.0 {
+            Ordering::Less
+        } else {
+            Ordering::Greater
+        });
+    }
+    let floor = f.floor();
+    match BigInt::from_f64(floor).map(|whole| b.cmp(&whole)) {
+        Some(Ordering::Equal) if floor != f => Some(Ordering::Less),
+        o => o,
+    }
+}
+
+/// Integers and floats compare by mathematical value, so `1 == 1.0` and
+/// `2 < 2.5`. NaN is unordered against everything, and non-numbers have no
+/// numeric ordering.
+fn compare_numbers(a: &LiteralType, b: &LiteralType) -> Option<Ordering> {
+    match (a, b) {
+        (LiteralType::Integer { lit: l }, LiteralType::Integer { lit: r }) => Some(l.cmp(r)),
+        (LiteralType::Float { lit: l }, LiteralType::Float { lit: r }) => l.partial_cmp(r),
+        (LiteralType::Integer { lit: l }, LiteralType::Float { lit: r }) => {
+            compare_integer_float(*l, *r)
+        }
+        (LiteralType::Float { lit: l }, LiteralType::Integer { lit: r }) => {
+            compare_integer_float(*r, *l).map(Ordering::reverse)
+        }
+        (LiteralType::BigInt { lit: l }, LiteralType::BigInt { lit: r }) => Some(l.cmp(r)),
+        (LiteralType::BigInt { lit: l }, LiteralType::Integer { lit: r }) => {
+            Some(l.cmp(&BigInt::from_i64(*r)))
+        }
+        (LiteralType::Integer { lit: l }, LiteralType::BigInt { lit: r }) => {
+            Some(BigInt::from_i64(*l).cmp(r))
+        }
+        (LiteralType::BigInt { lit: l }, LiteralType::Float { lit: r }) => compare_big_float(l, *r),
+        (LiteralType::Float { .. }, LiteralType::BigInt { .. }) => {
+            compare_numbers(b, a).map(Ordering::reverse)
+        }
+        _ => None,
+    }
+}
+
+fn check_number_operands<'src>(
+    left: &LiteralType<'src>,
+    right: &LiteralType<'src>,
+    operator: &Token<'src>,
+) -> Result<bool, InterpreterError<'src>> {
+    let rv = is_number(left) && is_number(right);
     if rv {
         Ok(rv)
     } else {
         Err(InterpreterError {
             tok: operator.clone(),
             msg: "Operands must be numbers.".to_string(),
+            thrown: None,
         })
     }
 }
 
-fn check_same_literal_type(
-    left: &LiteralType,
-    right: &LiteralType,
-    operator: &Token,
-) -> Result<bool, InterpreterError> {
-    let mut rv = true;
-    match left {
-        LiteralType::Float { lit } => match right {
-            LiteralType::Float { lit } => rv &= true,
-            _ => rv &= false,
-        },
-        LiteralType::String { lit } => match right {
-            LiteralType::String { lit } => rv &= true,
-            _ => rv &= false,
-        },
-        _ => rv &= false,
-    }
+fn check_same_literal_type<'src>(
+    left: &LiteralType<'src>,
+    right: &LiteralType<'src>,
+    operator: &Token<'src>,
+) -> Result<bool, InterpreterError<'src>> {
+    let rv = (is_number(left) && is_number(right))
+        || matches!(
+            (left, right),
+            (LiteralType::String { .. }, LiteralType::String { .. })
+        );
     if rv {
         Ok(rv)
     } else {
         Err(InterpreterError {
             tok: operator.clone(),
             msg: "Operands must be two numbers or two strings.".to_string(),
+            thrown: None,
         })
     }
 }
 
-fn evaluate_binary(
-    left: Box<Expression>,
-    right: Box<Expression>,
-    operator: Token,
-) -> Result<LiteralType, InterpreterError> {
-    let left_lit = evaluate(*left)?;
-    let right_lit = evaluate(*right)?;
+/// Bitwise operators and `div` work on the integral subset of numbers that
+/// fits in an i64.
+pub fn to_integer<'src>(
+    lit: &LiteralType<'src>,
+    operator: &Token<'src>,
+    msg: &str,
+) -> Result<i64, InterpreterError<'src>> {
+    let integer = match lit {
+        LiteralType::Integer { lit } => Some(*lit),
+        LiteralType::BigInt { lit } => lit.to_i64(),
+        LiteralType::Float { lit }
+            if lit.fract() == 0.0 && *lit >= i64::MIN as f64 && *lit < i64::MAX as f64 =>
+        {
+            Some(*lit as i64)
+        }
+        _ => None,
+    };
+    integer.ok_or_else(|| InterpreterError {
+        tok: operator.clone(),
+        msg: msg.to_string(),
+        thrown: None,
+    })
+}
 
-    if matches!(
-        operator.tok_type,
-        TokenType::Greater
-            | TokenType::GreaterEqual
-            | TokenType::Less
-            | TokenType::LessEqual
-            | TokenType::Slash
-            | TokenType::Minus
-            | TokenType::Star
-    ) {
-        check_number_operands(&left_lit, &right_lit, &operator)?;
-    }
-
-    if operator.tok_type == TokenType::Plus {
-        check_same_literal_type(&left_lit, &right_lit, &operator)?;
-    }
-
-    let lt = match left_lit {
-        LiteralType::Float { lit: l } => match right_lit {
-            LiteralType::Float { lit: r } => match operator.tok_type {
-                TokenType::Plus => LiteralType::Float { lit: l + r },
-                TokenType::Minus => LiteralType::Float { lit: l - r },
-                TokenType::Slash => LiteralType::Float { lit: l / r },
-                TokenType::Star => LiteralType::Float { lit: l * r },
-                TokenType::Greater => LiteralType::Bool { lit: l > r },
-                TokenType::GreaterEqual => LiteralType::Bool { lit: l >= r },
-                TokenType::Less => LiteralType::Bool { lit: l < r },
-                TokenType::LessEqual => LiteralType::Bool { lit: l <= r },
-                TokenType::BangEqual => LiteralType::Bool {
-                    lit: !is_equal(left_lit, right_lit),
-                },
-                TokenType::EqualEqual => LiteralType::Bool {
-                    lit: is_equal(left_lit, right_lit),
+fn evaluate_integer_binary<'src>(
+    left: &LiteralType<'src>,
+    right: &LiteralType<'src>,
+    operator: &Token<'src>,
+) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+    if operator.tok_type == TokenType::Div && (is_big(left) || is_big(right)) {
+        return evaluate_arithmetic(left, right, operator);
+    }
+    let msg = "Operands must be integers.";
+    let l = to_integer(left, operator, msg)?;
+    let r = to_integer(right, operator, msg)?;
+    let shift_amount = || match u32::try_from(r) {
+        Ok(r) if r < i64::BITS => Ok(r),
+        _ => Err(InterpreterError {
+            tok: operator.clone(),
+            msg: "Shift amount must be between 0 and 63.".to_string(),
+            thrown: None,
+        }),
+    };
+    let v = match operator.tok_type {
+        TokenType::Ampersand => l & r,
+        TokenType::Pipe => l | r,
+        TokenType::Caret => l ^ r,
+        TokenType::LessLess => l << shift_amount()?,
+        TokenType::GreaterGreater => l >> shift_amount()?,
+        TokenType::Div => match l.checked_div(r) {
+            Some(v) => v,
+            None if r == 0 => return Err(division_by_zero(operator)),
+            None => return Err(integer_overflow(operator)),
+        },
+        _ => {
+            return Err(InterpreterError {
+                tok: operator.clone(),
+                msg: "interpreter - Got TokenType other than an integer operator".to_string(),
+                thrown: None,
+            })
+        }
+    };
+    Ok(LiteralType::Integer { lit: v })
+}
+
+fn integer_overflow<'src>(operator: &Token<'src>) -> InterpreterError<'src> {
+    InterpreterError {
+        tok: operator.clone(),
+        msg: "Integer overflow.".to_string(),
+        thrown: None,
+    }
+}
+
+fn division_by_zero<'src>(operator: &Token<'src>) -> InterpreterError<'src> {
+    InterpreterError {
+        tok: operator.clone(),
+        msg: "Division by zero.".to_string(),
+        thrown: None,
+    }
+}
+
+fn is_big(lit: &LiteralType) -> bool {
+    matches!(lit, LiteralType::BigInt { .. })
+}
+
+fn to_big(lit: &LiteralType) -> Option<BigInt> {
+    match lit {
+        LiteralType::Integer { lit } => Some(BigInt::from_i64(*lit)),
+        LiteralType::BigInt { lit } => Some(lit.clone()),
+        _ => None,
+    }
+}
+
+/// Largest result big integer `**` computes, in bits. Anything bigger would
+/// take too long to compute and print to be of use.
+const MAX_POW_BITS: u64 = 1 << 16;
+
+/// `l ** r` for a non-negative `r`. Bases 0, 1 and -1 give a small result
+/// for any exponent; other results longer than `MAX_POW_BITS` are an error.
+fn big_pow<'src>(
+    l: &BigInt,
+    r: &BigInt,
+    operator: &Token<'src>,
+) -> Result<BigInt, InterpreterError<'src>> {
+    if r.is_zero() {
+        return Ok(BigInt::from_i64(1));
+    }
+    match l.to_i64() {
+        Some(0) | Some(1) => return Ok(l.clone()),
+        Some(-1) if r.is_odd() => return Ok(l.clone()),
+        Some(-1) => return Ok(-l),
+        _ => {}
+    }
+    // |l| is at least 2^(bits - 1), so the result has at least
+    // (bits - 1) * exp + 1 bits.
+    let exp = r.to_i64().and_then(|e| u32::try_from(e).ok());
+    match exp {
+        Some(exp) if (l.bits() - 1) * u64::from(exp) < MAX_POW_BITS => Ok(l.pow(exp)),
+        _ => Err(InterpreterError {
+            tok: operator.clone(),
+            msg: format!("Result of '**' would have more than {} bits.", MAX_POW_BITS),
+            thrown: None,
+        }),
+    }
+}
+
+/// Big integer arithmetic is exact: `div` and `%` truncate like they do for
+/// integers. `/` and negative exponents fall back to floats as with integers.
+fn evaluate_big_arithmetic<'src>(
+    l: BigInt,
+    r: BigInt,
+    operator: &Token<'src>,
+) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+    if operator.tok_type == TokenType::StarStar && r >= BigInt::from_i64(0) {
+        return Ok(LiteralType::BigInt {
+            lit: big_pow(&l, &r, operator)?,
+        });
+    }
+    let lit = match operator.tok_type {
+        TokenType::Plus => &l + &r,
+        TokenType::Minus => &l - &r,
+        TokenType::Star => &l * &r,
+        TokenType::Div | TokenType::Percent => match l.div_rem(&r) {
+            Some((q, _)) if operator.tok_type == TokenType::Div => q,
+            Some((_, rem)) => rem,
+            None => return Err(division_by_zero(operator)),
+        },
+        _ => {
+            return evaluate_arithmetic(
+                &LiteralType::Float { lit: l.to_f64() },
+                &LiteralType::Float { lit: r.to_f64() },
+                operator,
+            )
+        }
+    };
+    Ok(LiteralType::BigInt { lit })
+}
+
+fn as_float(lit: &LiteralType) -> f64 {
+    match lit {
+        LiteralType::Integer { lit } => *lit as f64,
+        LiteralType::BigInt { lit } => lit.to_f64(),
+        LiteralType::Float { lit } => *lit,
+        _ => f64::NAN,
+    }
+}
+
+/// Arithmetic on two integers stays integral and reports overflow, except
+/// that `/` always divides as floats. A big integer operand promotes an
+/// integer to a big integer, and any float operand promotes the other.
+fn evaluate_arithmetic<'src>(
+    left: &LiteralType<'src>,
+    right: &LiteralType<'src>,
+    operator: &Token<'src>,
+) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+    if is_big(left) || is_big(right) {
+        if let (Some(l), Some(r)) = (to_big(left), to_big(right)) {
+            return evaluate_big_arithmetic(l, r, operator);
+        }
+    }
+    if let (LiteralType::Integer { lit: l }, LiteralType::Integer { lit: r }) = (left, right) {
+        let (l, r) = (*l, *r);
+        let v = match operator.tok_type {
+            TokenType::Plus => l.checked_add(r),
+            TokenType::Minus => l.checked_sub(r),
+            TokenType::Star => l.checked_mul(r),
+            TokenType::Percent if r == 0 => return Err(division_by_zero(operator)),
+            // Only i64::MIN % -1 wraps, and its remainder is 0.
+            TokenType::Percent => Some(l.wrapping_rem(r)),
+            TokenType::StarStar if r >= 0 => match u32::try_from(r) {
+                Ok(r) => l.checked_pow(r),
+                // Only 0, 1 and -1 stay in range for an exponent this large.
+                Err(_) => match l {
+                    0 | 1 => Some(l),
+                    -1 if r % 2 == 0 => Some(1),
+                    -1 => Some(-1),
+                    _ => None,
                 },
-                _ => todo!(),
             },
             _ => {
-                panic!()
+                return evaluate_arithmetic(
+                    &LiteralType::Float { lit: l as f64 },
+                    &LiteralType::Float { lit: r as f64 },
+                    operator,
+                )
             }
-        },
-        LiteralType::String { lit: l } => match right_lit {
-            LiteralType::String { lit: r } => match operator.tok_type {
-                TokenType::Plus => LiteralType::String {
-                    lit: l + r.as_str(),
-                },
-                TokenType::BangEqual => LiteralType::Bool { lit: !(l == r) },
-                TokenType::EqualEqual => LiteralType::Bool { lit: l == r },
-                _ => todo!(),
-            },
-            _ => todo!(),
-        },
-        _ => todo!(),
+        };
+        return match v {
+            Some(lit) => Ok(LiteralType::Integer { lit }),
+            None => Err(integer_overflow(operator)),
+        };
+    }
+
+    let l = as_float(left);
+    let r = as_float(right);
+    let lit = match operator.tok_type {
+        TokenType::Plus => l + r,
+        TokenType::Minus => l - r,
+        TokenType::Slash => l / r,
+        TokenType::Star => l * r,
+        TokenType::Percent => l % r,
+        TokenType::StarStar => l.powf(r),
+        _ => {
+            return Err(InterpreterError {
+                tok: operator.clone(),
+                msg: "interpreter - Got TokenType other than an arithmetic operator".to_string(),
+                thrown: None,
+            })
+        }
     };
+    Ok(LiteralType::Float { lit })
+}
+
+/// How a statement finished. Anything but `Normal` unwinds the enclosing
+/// statements up to the loop or function that handles it.
+enum Flow<'src> {
+    Normal,
+    Break,
+    Continue,
+    Return { value: LiteralType<'src> },
+}
+
+/// The values a for-in loop steps through. A list is read live, so elements
+/// pushed during the loop are visited; map keys and string characters are
+/// collected up front. A range is counted through as the loop goes, and so
+/// is an iterator object, whose `next()` returns `StopIteration` once it is
+/// exhausted.
+enum LoopValues<'src> {
+    List {
+        list: Rc<RefCell<List<'src>>>,
+        index: usize,
+    },
+    Range {
+        range: Range,
+    },
+    Values {
+        values: std::vec::IntoIter<LiteralType<'src>>,
+    },
+    Iterator {
+        iterator: LiteralType<'src>,
+        done: bool,
+    },
+}
+
+impl<'src> LoopValues<'src> {
+    /// An instance is iterated through the object its `iter()` method
+    /// returns.
+    fn new(
+        iterable: LiteralType<'src>,
+        keyword: &Token<'src>,
+        interpreter: &mut Interpreter<'src>,
+    ) -> Result<LoopValues<'src>, InterpreterError<'src>> {
+        match iterable {
+            LiteralType::List { lit } => Ok(LoopValues::List {
+                list: lit,
+                index: 0,
+            }),
+            LiteralType::Map { lit } => Ok(LoopValues::Values {
+                values: lit.borrow().keys().into_iter(),
+            }),
+            LiteralType::Range { lit } => Ok(LoopValues::Range { range: lit }),
+            LiteralType::String { lit } => Ok(LoopValues::Values {
+                values: lit
+                    .chars()
+                    .map(|c| LiteralType::String { lit: c.to_string() })
+                    .collect::<Vec<_>>()
+                    .into_iter(),
+            }),
+            LiteralType::Instance { .. } => Ok(LoopValues::Iterator {
+                iterator: interpreter.call_method(iterable, "iter", keyword)?,
+                done: false,
+            }),
+            _ => Err(InterpreterError {
+                tok: keyword.clone(),
+                msg: "Can only iterate over lists, maps, ranges, strings and instances."
+                    .to_string(),
+                thrown: None,
+            }),
+        }
+    }
+
+    fn next_value(
+        &mut self,
+        keyword: &Token<'src>,
+        interpreter: &mut Interpreter<'src>,
+    ) -> Result<Option<LiteralType<'src>>, InterpreterError<'src>> {
+        match self {
+            LoopValues::List { list, index } => {
+                let value = list.borrow().get(*index).cloned();
+                *index += 1;
+                Ok(value)
+            }
+            LoopValues::Range { range } => Ok(range.next().map(|lit| LiteralType::Integer { lit })),
+            LoopValues::Values { values } => Ok(values.next()),
+            LoopValues::Iterator { done: true, .. } => Ok(None),
+            LoopValues::Iterator { iterator, done } => {
+                let value = interpreter.call_method(iterator.clone(), "next", keyword)?;
+                match value {
+                    LiteralType::Class { lit } if Rc::ptr_eq(&lit, &interpreter.stop_iteration) => {
+                        *done = true;
+                        Ok(None)
+                    }
+                    value => Ok(Some(value)),
+                }
+            }
+        }
+    }
+}
 
-    Ok(lt)
+pub struct Interpreter<'src> {
+    strict_arithmetic: bool,
+    environment: Rc<RefCell<Environment<'src>>>,
+    // The file the running code was written in, if any.
+    file: Option<Rc<PathBuf>>,
+    // Namespaces of the modules loaded so far, by canonical path.
+    modules: HashMap<PathBuf, Rc<RefCell<Map<'src>>>>,
+    // The script and the modules currently being loaded, outermost first.
+    importing: Vec<PathBuf>,
+    // The list of command line arguments given after the script path.
+    args: LiteralType<'src>,
+    // What an iterator's `next()` returns when it has no more values,
+    // shared by the script and its modules as the global `StopIteration`.
+    stop_iteration: Rc<Class<'src>>,
 }
 
-fn evaluate_unary(
-    operator: &Token,
-    right: Box<Expression>,
-) -> Result<LiteralType, InterpreterError> {
-    let r = right;
-    let o = operator;
-    let right = evaluate(*r)?;
-    match o.tok_type {
-        TokenType::Bang => {
-            let truthy = is_truthy(&right);
-            Ok(LiteralType::Bool { lit: !truthy })
+impl<'src> Interpreter<'src> {
+    /// With `strict_arithmetic` set, dividing by zero or producing NaN is a
+    /// runtime error instead of yielding an IEEE infinity or NaN.
+    ///
+    /// `script` is the file being run, which imports are resolved against;
+    /// without one they are resolved against the working directory.
+    ///
+    /// `args` are the script's command line arguments, which the script and
+    /// its modules see as the global list `args`.
+    pub fn new(
+        strict_arithmetic: bool,
+        script: Option<&Path>,
+        args: Vec<String>,
+    ) -> Interpreter<'src> {
+        let script = script.and_then(|p| p.canonicalize().ok());
+        let args = args
+            .into_iter()
+            .map(|lit| LiteralType::String { lit })
+            .collect();
+        let mut interpreter = Interpreter {
+            strict_arithmetic,
+            environment: Rc::new(RefCell::new(Environment::new())),
+            file: script.clone().map(Rc::new),
+            modules: HashMap::new(),
+            importing: script.into_iter().collect(),
+            args: LiteralType::List {
+                lit: Rc::new(RefCell::new(List::new(args))),
+            },
+            stop_iteration: Rc::new(Class {
+                name: "StopIteration",
+                methods: Methods::default(),
+                metaclass: None,
+            }),
+        };
+        interpreter.environment = Rc::new(RefCell::new(interpreter.globals()));
+        interpreter
+    }
+
+    /// A fresh global scope, for the script or a module.
+    fn globals(&self) -> Environment<'src> {
+        let mut globals = Environment::new();
+        define_natives(&mut globals);
+        globals.define("args", self.args.clone());
+        globals.define(
+            "StopIteration",
+            LiteralType::Class {
+                lit: Rc::clone(&self.stop_iteration),
+            },
+        );
+        globals
+    }
+
+    fn evaluate_binary(
+        &mut self,
+        left: &Expression<'src>,
+        right: &Expression<'src>,
+        operator: &Token<'src>,
+    ) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+        let left_lit = self.evaluate(left)?;
+        let right_lit = self.evaluate(right)?;
+        self.apply_binary(left_lit, right_lit, operator)
+    }
+
+    fn apply_binary(
+        &mut self,
+        left_lit: LiteralType<'src>,
+        right_lit: LiteralType<'src>,
+        operator: &Token<'src>,
+    ) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+        if let Some(result) = self.apply_special(&left_lit, &right_lit, operator)? {
+            return Ok(result);
+        }
+        if matches!(
+            operator.tok_type,
+            TokenType::Ampersand
+                | TokenType::Pipe
+                | TokenType::Caret
+                | TokenType::LessLess
+                | TokenType::GreaterGreater
+                | TokenType::Div
+        ) {
+            return evaluate_integer_binary(&left_lit, &right_lit, operator);
+        }
+
+        match operator.tok_type {
+            TokenType::EqualEqual => Ok(LiteralType::Bool {
+                lit: is_equal(left_lit, right_lit),
+            }),
+            TokenType::BangEqual => Ok(LiteralType::Bool {
+                lit: !is_equal(left_lit, right_lit),
+            }),
+            TokenType::Greater
+            | TokenType::GreaterEqual
+            | TokenType::Less
+            | TokenType::LessEqual => {
+                check_number_operands(&left_lit, &right_lit, operator)?;
+                let ord = compare_numbers(&left_lit, &right_lit);
+                let lit = match operator.tok_type {
+                    TokenType::Greater => ord == Some(Ordering::Greater),
+                    TokenType::GreaterEqual => {
+                        matches!(ord, Some(Ordering::Greater | Ordering::Equal))
+                    }
+                    TokenType::Less => ord == Some(Ordering::Less),
+                    _ => matches!(ord, Some(Ordering::Less | Ordering::Equal)),
+                };
+                Ok(LiteralType::Bool { lit })
+            }
+            TokenType::Plus => {
+                check_same_literal_type(&left_lit, &right_lit, operator)?;
+                match (left_lit, right_lit) {
+                    (LiteralType::String { lit: l }, LiteralType::String { lit: r }) => {
+                        Ok(LiteralType::String {
+                            lit: l + r.as_str(),
+                        })
+                    }
+                    (l, r) => {
+                        let result = evaluate_arithmetic(&l, &r, operator)?;
+                        self.check_strict_arithmetic(&r, result, operator)
+                    }
+                }
+            }
+            _ => {
+                check_number_operands(&left_lit, &right_lit, operator)?;
+                let result = evaluate_arithmetic(&left_lit, &right_lit, operator)?;
+                self.check_strict_arithmetic(&right_lit, result, operator)
+            }
+        }
+    }
+
+    /// Runs a binary operator through the special methods of an instance
+    /// operand: `__add__`, `__sub__` and `__mul__` of the left one, `__eq__`
+    /// of either, and `__lt__` of the left one, which together with `==`
+    /// gives the other comparisons. Returns `None` when none applies.
+    fn apply_special(
+        &mut self,
+        left: &LiteralType<'src>,
+        right: &LiteralType<'src>,
+        operator: &Token<'src>,
+    ) -> Result<Option<LiteralType<'src>>, InterpreterError<'src>> {
+        let name = match operator.tok_type {
+            TokenType::Plus => "__add__",
+            TokenType::Minus => "__sub__",
+            TokenType::Star => "__mul__",
+            TokenType::EqualEqual | TokenType::BangEqual => {
+                let mut equal = self.call_special(left, "__eq__", vec![right.clone()], operator)?;
+                if equal.is_none() {
+                    equal = self.call_special(right, "__eq__", vec![left.clone()], operator)?;
+                }
+                return Ok(equal.map(|equal| LiteralType::Bool {
+                    lit: is_truthy(&equal) == (operator.tok_type == TokenType::EqualEqual),
+                }));
+            }
+            TokenType::Less
+            | TokenType::LessEqual
+            | TokenType::Greater
+            | TokenType::GreaterEqual => {
+                let less = match self.call_special(left, "__lt__", vec![right.clone()], operator)? {
+                    Some(less) => is_truthy(&less),
+                    None => return Ok(None),
+                };
+                let lit = match operator.tok_type {
+                    TokenType::Less => less,
+                    TokenType::GreaterEqual => !less,
+                    tok_type => {
+                        let mut op = operator.clone();
+                        op.tok_type = TokenType::EqualEqual;
+                        let equal = self.apply_binary(left.clone(), right.clone(), &op)?;
+                        let less_equal = less || is_truthy(&equal);
+                        match tok_type {
+                            TokenType::LessEqual => less_equal,
+                            _ => !less_equal,
+                        }
+                    }
+                };
+                return Ok(Some(LiteralType::Bool { lit }));
+            }
+            _ => return Ok(None),
+        };
+        self.call_special(left, name, vec![right.clone()], operator)
+    }
+
+    /// Calls the special method `name` of `object` if it is an instance
+    /// whose class defines one, and returns `None` otherwise.
+    fn call_special(
+        &mut self,
+        object: &LiteralType<'src>,
+        name: &str,
+        args: Vec<LiteralType<'src>>,
+        tok: &Token<'src>,
+    ) -> Result<Option<LiteralType<'src>>, InterpreterError<'src>> {
+        let method = match object {
+            LiteralType::Instance { lit } => {
+                lit.borrow().class.methods.get(MethodKind::Method, name)
+            }
+            _ => None,
+        };
+        match method {
+            Some(method) => {
+                let bound = method.bind(object.clone());
+                Ok(Some(self.call_function(&bound, args, Vec::new(), tok)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Formats a value like `stringify`, except that an instance whose
+    /// class defines `__str__` shows as the string that method returns.
+    pub fn stringify(
+        &mut self,
+        value: &LiteralType<'src>,
+    ) -> Result<String, InterpreterError<'src>> {
+        // The call isn't written anywhere in the program, so errors from
+        // making it, rather than from running the method, have no line.
+        let tok = Token {
+            tok_type: TokenType::Identifier,
+            lexeme: "__str__",
+            line: 0,
+            literal: None,
+        };
+        let mut error = None;
+        let text = stringify_nested(value, &mut Vec::new(), &mut |lit| {
+            if error.is_some() {
+                return None;
+            }
+            match self.call_special(lit, "__str__", Vec::new(), &tok) {
+                Ok(Some(LiteralType::String { lit })) => Some(lit),
+                Ok(Some(_)) => {
+                    error = Some(InterpreterError {
+                        tok: tok.clone(),
+                        msg: "'__str__' must return a string.".to_string(),
+                        thrown: None,
+                    });
+                    None
+                }
+                Ok(None) => None,
+                Err(e) => {
+                    error = Some(e);
+                    None
+                }
+            }
+        });
+        match error {
+            Some(e) => Err(e),
+            None => Ok(text),
+        }
+    }
+
+    fn check_strict_arithmetic(
+        &self,
+        right: &LiteralType<'src>,
+        result: LiteralType<'src>,
+        operator: &Token<'src>,
+    ) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+        if !self.strict_arithmetic {
+            return Ok(result);
+        }
+        if matches!(operator.tok_type, TokenType::Slash | TokenType::Percent)
+            && compare_numbers(right, &LiteralType::Integer { lit: 0 }) == Some(Ordering::Equal)
+        {
+            return Err(division_by_zero(operator));
+        }
+        match result {
+            LiteralType::Float { lit } if lit.is_nan() => Err(InterpreterError {
+                tok: operator.clone(),
+                msg: "Arithmetic result is not a number.".to_string(),
+                thrown: None,
+            }),
+            _ => Ok(result),
         }
-        TokenType::Minus => match right {
-            LiteralType::Float { lit } => Ok(LiteralType::Float { lit: (lit * -1.0) }),
+    }
+
+    fn evaluate_unary(
+        &mut self,
+        operator: &Token<'src>,
+        right: &Expression<'src>,
+    ) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+        let o = operator;
+        let right = self.evaluate(right)?;
+        match o.tok_type {
+            TokenType::Bang => {
+                let truthy = is_truthy(&right);
+                Ok(LiteralType::Bool { lit: !truthy })
+            }
+            TokenType::Minus => match right {
+                LiteralType::Integer { lit } => match lit.checked_neg() {
+                    Some(lit) => Ok(LiteralType::Integer { lit }),
+                    None => Err(integer_overflow(o)),
+                },
+                LiteralType::BigInt { lit } => Ok(LiteralType::BigInt { lit: -&lit }),
+                LiteralType::Float { lit } => Ok(LiteralType::Float { lit: -lit }),
+                _ => Err(InterpreterError {
+                    tok: o.clone(),
+                    msg: "Operand must be a number.".to_string(),
+                    thrown: None,
+                }),
+            },
+            TokenType::Tilde => {
+                let i = to_integer(&right, o, "Operand must be an integer.")?;
+                Ok(LiteralType::Integer { lit: !i })
+            }
             _ => Err(InterpreterError {
                 tok: o.clone(),
-                msg: "Operand must be a number.".to_string(),
+                msg: "interpreter - Got TokenType other than Minus, Bang or Tilde for unary"
+                    .to_string(),
+                thrown: None,
             }),
-        },
-        _ => Err(InterpreterError {
-            tok: o.clone(),
-            msg: "interpreter - Got TokenType other than Minus or Bang for unary".to_string(),
-        }),
+        }
+    }
+
+    /// `=` stores the value as is; a compound operator such as `+=` combines
+    /// it with the current value exactly like the matching binary operator.
+    fn evaluate_assign(
+        &mut self,
+        name: &Token<'src>,
+        operator: &Token<'src>,
+        value: &Expression<'src>,
+    ) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+        let mut value = self.evaluate(value)?;
+        if let Some(tok_type) = compound_operator(operator) {
+            let current = self.environment.borrow().get(name)?;
+            let mut op = operator.clone();
+            op.tok_type = tok_type;
+            value = self.apply_binary(current, value, &op)?;
+        }
+        self.environment.borrow_mut().assign(name, value.clone())?;
+        Ok(value)
+    }
+
+    fn evaluate_call(
+        &mut self,
+        callee: &Expression<'src>,
+        paren: &Token<'src>,
+        arguments: &[Expression<'src>],
+        named: &[(Token<'src>, Expression<'src>)],
+    ) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+        let callee = self.evaluate(callee)?;
+        let mut args = Vec::new();
+        for a in arguments {
+            args.push(self.evaluate(a)?);
+        }
+        let mut named_args = Vec::new();
+        for (name, value) in named {
+            named_args.push((name, self.evaluate(value)?));
+        }
+        self.call_value(callee, args, named_args, paren)
+    }
+
+    fn call_value(
+        &mut self,
+        callee: LiteralType<'src>,
+        args: Vec<LiteralType<'src>>,
+        named_args: Vec<(&Token<'src>, LiteralType<'src>)>,
+        paren: &Token<'src>,
+    ) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+        match callee {
+            LiteralType::Function { lit } => self.call_function(&lit, args, named_args, paren),
+            LiteralType::Class { lit } => self.instantiate(lit, args, named_args, paren),
+            LiteralType::Native { lit } => match named_args.first() {
+                Some((name, _)) => Err(InterpreterError {
+                    tok: (*name).clone(),
+                    msg: "Can't pass named arguments to a native function.".to_string(),
+                    thrown: None,
+                }),
+                None => self.call_native(lit, args, paren),
+            },
+            _ => Err(InterpreterError {
+                tok: paren.clone(),
+                msg: "Can only call functions and classes.".to_string(),
+                thrown: None,
+            }),
+        }
+    }
+
+    /// Positional arguments fill the parameters in order, with any surplus
+    /// going to the rest parameter. Named arguments then fill parameters by
+    /// name, and parameters still without a value take their defaults.
+    fn call_function(
+        &mut self,
+        function: &Function<'src>,
+        args: Vec<LiteralType<'src>>,
+        named: Vec<(&Token<'src>, LiteralType<'src>)>,
+        paren: &Token<'src>,
+    ) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+        let declaration = &function.declaration;
+        let params = &declaration.params;
+        let required = params.iter().filter(|p| p.default.is_none()).count();
+        let max = match declaration.rest {
+            Some(_) => None,
+            None => Some(params.len()),
+        };
+        if max.is_some_and(|max| args.len() > max) {
+            return Err(arity_error(paren, required, max, args.len() + named.len()));
+        }
+
+        // Positional arguments fill parameters from the left, then named
+        // arguments fill the parameters they name. Only then is it known
+        // which parameters are still missing.
+        let positional = args.len();
+        let mut args = args.into_iter();
+        let mut values: Vec<Option<LiteralType<'src>>> =
+            params.iter().map(|_| args.next()).collect();
+        let surplus: Vec<LiteralType<'src>> = args.collect();
+        let named_count = named.len();
+        for (name, value) in named {
+            let msg = match params.iter().position(|p| p.name.lexeme == name.lexeme) {
+                Some(i) if values[i].is_none() => {
+                    values[i] = Some(value);
+                    continue;
+                }
+                Some(_) => format!("Got multiple values for parameter '{}'.", name.lexeme),
+                None => format!("Unknown parameter '{}'.", name.lexeme),
+            };
+            return Err(InterpreterError {
+                tok: name.clone(),
+                msg,
+                thrown: None,
+            });
+        }
+        let missing = params
+            .iter()
+            .zip(&values)
+            .find(|(param, value)| value.is_none() && param.default.is_none());
+        if let Some((param, _)) = missing {
+            if named_count == 0 {
+                return Err(arity_error(paren, required, max, positional));
+            }
+            return Err(InterpreterError {
+                tok: paren.clone(),
+                msg: format!("Missing argument for parameter '{}'.", param.name.lexeme),
+                thrown: None,
+            });
+        }
+
+        let environment = Environment::new_enclosed(Rc::clone(&function.closure));
+        let previous = std::mem::replace(&mut self.environment, Rc::new(RefCell::new(environment)));
+        let previous_file = std::mem::replace(&mut self.file, function.file.clone());
+        let result = self
+            .bind_parameters(declaration, values, surplus)
+            .and_then(|()| self.execute_body(&declaration.body));
+        self.environment = previous;
+        self.file = previous_file;
+        result
+    }
+
+    /// Defines the parameters in the callee's environment, which is current.
+    /// Every parameter without a default has a value by now. Defaults are
+    /// evaluated there in order, so they can refer to the parameters before
+    /// them.
+    fn bind_parameters(
+        &mut self,
+        declaration: &Lambda<'src>,
+        values: Vec<Option<LiteralType<'src>>>,
+        surplus: Vec<LiteralType<'src>>,
+    ) -> Result<(), InterpreterError<'src>> {
+        for (param, value) in declaration.params.iter().zip(values) {
+            let value = match (value, &param.default) {
+                (Some(value), _) => value,
+                (None, Some(default)) => self.evaluate(default)?,
+                (None, None) => panic!("bind_parameters: required parameter without a value"),
+            };
+            self.environment
+                .borrow_mut()
+                .define(param.name.lexeme, value);
+        }
+        if let Some(rest) = &declaration.rest {
+            let rest_list = LiteralType::List {
+                lit: Rc::new(RefCell::new(List::new(surplus))),
+            };
+            self.environment.borrow_mut().define(rest.lexeme, rest_list);
+        }
+        Ok(())
+    }
+
+    fn call_native(
+        &mut self,
+        native: NativeFunction<'src>,
+        args: Vec<LiteralType<'src>>,
+        paren: &Token<'src>,
+    ) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+        if args.len() < native.min_arity || args.len() > native.max_arity {
+            return Err(arity_error(
+                paren,
+                native.min_arity,
+                Some(native.max_arity),
+                args.len(),
+            ));
+        }
+        (native.function)(paren, args)
     }
-}
 
-pub fn evaluate(expr: Expression) -> Result<LiteralType, InterpreterError> {
-    let lt = match expr {
-        Expression::Binary {
-            left,
-            operator,
-            right,
-        } => evaluate_binary(left, right, operator),
-        Expression::Unary { operator, right } => evaluate_unary(&operator, right),
-        Expression::Grouping { group } => {
-            return evaluate(*group);
+    /// `xs[i]` reads a list element; `m[k]` looks a key up in a map; an
+    /// instance whose class defines `__index__` is indexed by calling it.
+    fn evaluate_index(
+        &mut self,
+        object: &Expression<'src>,
+        bracket: &Token<'src>,
+        index: &Expression<'src>,
+    ) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+        let object = self.evaluate(object)?;
+        let index = self.evaluate(index)?;
+        if let Some(value) =
+            self.call_special(&object, "__index__", vec![index.clone()], bracket)?
+        {
+            return Ok(value);
         }
-        Expression::Literal { lit } => {
-            if matches!(lit.tok_type, TokenType::False | TokenType::True) {
-                return Ok(LiteralType::Bool {
-                    lit: lit.bool_literal,
-                });
+        match object {
+            LiteralType::List { lit } => {
+                let list = lit.borrow();
+                let i = resolve_list_index(&index, bracket, list.len())?;
+                Ok(list[i].clone())
             }
-            if matches!(lit.tok_type, TokenType::String) {
-                return Ok(LiteralType::String {
-                    lit: lit.string_literal,
-                });
+            LiteralType::Map { lit } => {
+                let key = MapKey::new(&index, bracket)?;
+                match lit.borrow().get(&key) {
+                    Some(value) => Ok(value.clone()),
+                    None => Err(missing_key(bracket, &index)),
+                }
             }
-            if matches!(lit.tok_type, TokenType::Number) {
-                return Ok(LiteralType::Float {
-                    lit: lit.float_literal,
-                });
+            LiteralType::Range { lit } => {
+                let len = usize::try_from(lit.len()).unwrap_or(usize::MAX);
+                let i = resolve_list_index(&index, bracket, len)?;
+                match lit.get(i as u64) {
+                    Some(lit) => Ok(LiteralType::Integer { lit }),
+                    None => Err(InterpreterError {
+                        tok: bracket.clone(),
+                        msg: "Range index out of range.".to_string(),
+                        thrown: None,
+                    }),
+                }
             }
-            panic!("interpreter - could not match literal token type");
+            _ => Err(InterpreterError {
+                tok: bracket.clone(),
+                msg: "Only lists, maps and ranges can be indexed.".to_string(),
+                thrown: None,
+            }),
         }
-    };
-    lt
+    }
+
+    /// Stores into a list element or a map entry, adding the entry if the
+    /// key is new. As with variables, a compound operator combines the value
+    /// with the current one first, so the element or entry must exist.
+    fn evaluate_set_index(
+        &mut self,
+        object: &Expression<'src>,
+        bracket: &Token<'src>,
+        index: &Expression<'src>,
+        operator: &Token<'src>,
+        value: &Expression<'src>,
+    ) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+        let object = self.evaluate(object)?;
+        let index = self.evaluate(index)?;
+        let value = self.evaluate(value)?;
+        self.store_index(object, bracket, index, operator, value)
+    }
+
+    fn store_index(
+        &mut self,
+        object: LiteralType<'src>,
+        bracket: &Token<'src>,
+        index: LiteralType<'src>,
+        operator: &Token<'src>,
+        mut value: LiteralType<'src>,
+    ) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+        match object {
+            LiteralType::List { lit } => {
+                let i = resolve_list_index(&index, bracket, lit.borrow().len())?;
+                if let Some(tok_type) = compound_operator(operator) {
+                    let current = lit.borrow()[i].clone();
+                    let mut op = operator.clone();
+                    op.tok_type = tok_type;
+                    value = self.apply_binary(current, value, &op)?;
+                }
+                lit.borrow().check_mutable(bracket)?;
+                lit.borrow_mut()[i] = value.clone();
+            }
+            LiteralType::Map { lit } => {
+                let key = MapKey::new(&index, bracket)?;
+                if let Some(tok_type) = compound_operator(operator) {
+                    let current = match lit.borrow().get(&key) {
+                        Some(current) => current.clone(),
+                        None => return Err(missing_key(bracket, &index)),
+                    };
+                    let mut op = operator.clone();
+                    op.tok_type = tok_type;
+                    value = self.apply_binary(current, value, &op)?;
+                }
+                lit.borrow().check_mutable(bracket)?;
+                lit.borrow_mut().insert(key, index, value.clone());
+            }
+            _ => {
+                return Err(InterpreterError {
+                    tok: bracket.clone(),
+                    msg: "Only lists and maps can be indexed.".to_string(),
+                    thrown: None,
+                })
+            }
+        }
+        Ok(value)
+    }
+
+    /// Stores `value` into a target. With `declare` set the names in it are
+    /// defined in the current scope, otherwise existing variables are
+    /// assigned. The parts of a list or map are bound left to right.
+    /// Calls `object.name()` with no arguments on behalf of the interpreter,
+    /// reporting errors at `tok`.
+    fn call_method(
+        &mut self,
+        object: LiteralType<'src>,
+        name: &'static str,
+        tok: &Token<'src>,
+    ) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+        let mut name_tok = tok.clone();
+        name_tok.lexeme = name;
+        let method = self.get_property(object, &name_tok)?;
+        self.call_value(method, Vec::new(), Vec::new(), tok)
+    }
+
+    /// Calling a class makes a new instance and runs its `init` method, if
+    /// it has one, with the arguments. A class without `init` takes none.
+    fn instantiate(
+        &mut self,
+        class: Rc<Class<'src>>,
+        args: Vec<LiteralType<'src>>,
+        named: Vec<(&Token<'src>, LiteralType<'src>)>,
+        paren: &Token<'src>,
+    ) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+        let init = class.methods.get(MethodKind::Method, "init");
+        let instance = LiteralType::Instance {
+            lit: Rc::new(RefCell::new(Instance::new(class))),
+        };
+        match init {
+            Some(init) => {
+                self.call_function(&init.bind(instance.clone()), args, named, paren)?;
+            }
+            None if !args.is_empty() || !named.is_empty() => {
+                return Err(arity_error(paren, 0, Some(0), args.len() + named.len()));
+            }
+            None => {}
+        }
+        Ok(instance)
+    }
+
+    /// Reads `object.name`. A getter comes first, then a field of an
+    /// instance, then a method, which comes back bound to the object.
+    fn get_property(
+        &mut self,
+        object: LiteralType<'src>,
+        name: &Token<'src>,
+    ) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+        let class = receiver_class(&object, name)?;
+        let members = &class.methods;
+        if let Some(getter) = members.get(MethodKind::Getter, name.lexeme) {
+            return self.call_function(&getter.bind(object), Vec::new(), Vec::new(), name);
+        }
+        if let LiteralType::Instance { lit } = &object {
+            if let Some(value) = lit.borrow().fields.get(name.lexeme) {
+                return Ok(value.clone());
+            }
+        }
+        match members.get(MethodKind::Method, name.lexeme) {
+            Some(method) => Ok(LiteralType::Function {
+                lit: method.bind(object),
+            }),
+            None => Err(InterpreterError {
+                tok: name.clone(),
+                msg: format!("Undefined property '{}'.", name.lexeme),
+                thrown: None,
+            }),
+        }
+    }
+
+    /// Stores into `object.name`, through a setter if there is one and
+    /// otherwise into a field of an instance. A compound operator combines
+    /// the value with what reading the property gives.
+    fn store_property(
+        &mut self,
+        object: LiteralType<'src>,
+        name: &Token<'src>,
+        operator: &Token<'src>,
+        mut value: LiteralType<'src>,
+    ) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+        if let Some(tok_type) = compound_operator(operator) {
+            let current = self.get_property(object.clone(), name)?;
+            let mut op = operator.clone();
+            op.tok_type = tok_type;
+            value = self.apply_binary(current, value, &op)?;
+        }
+        let class = receiver_class(&object, name)?;
+        if let Some(setter) = class.methods.get(MethodKind::Setter, name.lexeme) {
+            let args = vec![value.clone()];
+            self.call_function(&setter.bind(object), args, Vec::new(), name)?;
+            return Ok(value);
+        }
+        match object {
+            LiteralType::Instance { lit } => {
+                lit.borrow_mut().fields.insert(name.lexeme, value.clone());
+                Ok(value)
+            }
+            _ => Err(InterpreterError {
+                tok: name.clone(),
+                msg: "Only instances have fields.".to_string(),
+                thrown: None,
+            }),
+        }
+    }
+
+    fn bind(
+        &mut self,
+        target: &Target<'src>,
+        operator: &Token<'src>,
+        value: LiteralType<'src>,
+        declare: bool,
+    ) -> Result<(), InterpreterError<'src>> {
+        match target {
+            Target::Variable { name } => self.bind_name(name, value, declare),
+            Target::Index {
+                object,
+                bracket,
+                index,
+            } => {
+                let object = self.evaluate(object)?;
+                let index = self.evaluate(index)?;
+                self.store_index(object, bracket, index, operator, value)?;
+                Ok(())
+            }
+            Target::Property { object, name } => {
+                let object = self.evaluate(object)?;
+                self.store_property(object, name, operator, value)?;
+                Ok(())
+            }
+            Target::List {
+                bracket,
+                elements,
+                rest,
+            } => {
+                let list = expect_list(value, bracket, "Expected a list to destructure.")?;
+                let values = list.borrow().to_vec();
+                let expected = elements.len();
+                if rest.is_none() && values.len() != expected {
+                    return Err(InterpreterError {
+                        tok: bracket.clone(),
+                        msg: format!("Expected {} elements but got {}.", expected, values.len()),
+                        thrown: None,
+                    });
+                }
+                if values.len() < expected {
+                    return Err(InterpreterError {
+                        tok: bracket.clone(),
+                        msg: format!(
+                            "Expected at least {} elements but got {}.",
+                            expected,
+                            values.len()
+                        ),
+                        thrown: None,
+                    });
+                }
+                let mut values = values.into_iter();
+                for (element, value) in elements.iter().zip(values.by_ref()) {
+                    self.bind(element, operator, value, declare)?;
+                }
+                if let Some(rest) = rest {
+                    let rest_list = LiteralType::List {
+                        lit: Rc::new(RefCell::new(List::new(values.collect()))),
+                    };
+                    self.bind_name(rest, rest_list, declare)?;
+                }
+                Ok(())
+            }
+            Target::Fields { brace, names } => {
+                let map = match value {
+                    LiteralType::Map { lit } => lit,
+                    _ => {
+                        return Err(InterpreterError {
+                            tok: brace.clone(),
+                            msg: "Expected a map to destructure.".to_string(),
+                            thrown: None,
+                        })
+                    }
+                };
+                for name in names {
+                    let key = MapKey::String(name.lexeme.to_string());
+                    let value = match map.borrow().get(&key) {
+                        Some(value) => value.clone(),
+                        None => {
+                            return Err(InterpreterError {
+                                tok: name.clone(),
+                                msg: format!("Missing field '{}'.", name.lexeme),
+                                thrown: None,
+                            })
+                        }
+                    };
+                    self.bind_name(name, value, declare)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn bind_name(
+        &mut self,
+        name: &Token<'src>,
+        value: LiteralType<'src>,
+        declare: bool,
+    ) -> Result<(), InterpreterError<'src>> {
+        if declare {
+            self.environment.borrow_mut().define(name.lexeme, value);
+            Ok(())
+        } else {
+            self.environment.borrow_mut().assign(name, value)
+        }
+    }
+
+    /// `xs[a:b]` copies the elements from `a` up to but not including `b`
+    /// into a new list. Missing bounds default to the ends of the list.
+    fn evaluate_slice(
+        &mut self,
+        object: &Expression<'src>,
+        bracket: &Token<'src>,
+        start: &Option<Box<Expression<'src>>>,
+        end: &Option<Box<Expression<'src>>>,
+    ) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+        let object = self.evaluate(object)?;
+        let list = expect_list(object, bracket, "Only lists can be sliced.")?;
+        let mut bounds = [None, None];
+        for (bound, expr) in bounds.iter_mut().zip([start, end]) {
+            if let Some(expr) = expr {
+                let value = self.evaluate(expr)?;
+                *bound = Some(to_integer(
+                    &value,
+                    bracket,
+                    "Slice bounds must be integers.",
+                )?);
+            }
+        }
+
+        let list = list.borrow();
+        let len = list.len();
+        let start = bounds[0].map_or(0, |b| slice_bound(b, len));
+        let end = bounds[1].map_or(len, |b| slice_bound(b, len));
+        let elements = if start < end {
+            list[start..end].to_vec()
+        } else {
+            Vec::new()
+        };
+        Ok(LiteralType::List {
+            lit: Rc::new(RefCell::new(List::new(elements))),
+        })
+    }
+
+    /// Tries the arms in order and evaluates the body of the first whose
+    /// pattern matches and whose guard, if any, is true. The guard and body
+    /// see the pattern's bindings in a scope of their own.
+    fn evaluate_match(
+        &mut self,
+        keyword: &Token<'src>,
+        subject: &Expression<'src>,
+        arms: &[MatchArm<'src>],
+    ) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+        let value = self.evaluate(subject)?;
+        for arm in arms {
+            let mut bindings = Vec::new();
+            if !match_pattern(&arm.pattern, &value, &mut bindings) {
+                continue;
+            }
+
+            let mut environment = Environment::new_enclosed(Rc::clone(&self.environment));
+            for (name, value) in bindings {
+                environment.define(name, value);
+            }
+            let previous =
+                std::mem::replace(&mut self.environment, Rc::new(RefCell::new(environment)));
+            let result = self.evaluate_arm(arm);
+            self.environment = previous;
+            if let Some(result) = result? {
+                return Ok(result);
+            }
+        }
+        Err(InterpreterError {
+            tok: keyword.clone(),
+            msg: format!("No match arm matched {}.", stringify(&value)),
+            thrown: None,
+        })
+    }
+
+    /// Evaluates an arm whose pattern matched, or `None` if its guard fails.
+    fn evaluate_arm(
+        &mut self,
+        arm: &MatchArm<'src>,
+    ) -> Result<Option<LiteralType<'src>>, InterpreterError<'src>> {
+        if let Some(guard) = &arm.guard {
+            if !is_truthy(&self.evaluate(guard)?) {
+                return Ok(None);
+            }
+        }
+        self.evaluate(&arm.body).map(Some)
+    }
+
+    /// Runs a function body and returns the value of the first `return`
+    /// reached, or nil if the body runs to the end.
+    fn execute_body(
+        &mut self,
+        body: &[Statement<'src>],
+    ) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+        for statement in body {
+            if let Flow::Return { value } = self.execute(statement)? {
+                return Ok(value);
+            }
+        }
+        Ok(LiteralType::Nil)
+    }
+
+    fn execute(
+        &mut self,
+        statement: &Statement<'src>,
+    ) -> Result<Flow<'src>, InterpreterError<'src>> {
+        match statement {
+            Statement::Expression { expr } => {
+                self.evaluate(expr)?;
+                Ok(Flow::Normal)
+            }
+            Statement::Return { value } => {
+                let value = match value {
+                    Some(value) => self.evaluate(value)?,
+                    None => LiteralType::Nil,
+                };
+                Ok(Flow::Return { value })
+            }
+            Statement::Var { target, value } => {
+                match value {
+                    Some((equals, value)) => {
+                        let value = self.evaluate(value)?;
+                        self.bind(target, equals, value, true)?;
+                    }
+                    None => {
+                        if let Target::Variable { name } = target {
+                            self.environment
+                                .borrow_mut()
+                                .define(name.lexeme, LiteralType::Nil);
+                        }
+                    }
+                }
+                Ok(Flow::Normal)
+            }
+            Statement::Const { name, value } => {
+                let value = self.evaluate(value)?;
+                self.environment
+                    .borrow_mut()
+                    .define_const(name.lexeme, value);
+                Ok(Flow::Normal)
+            }
+            Statement::Block { statements } => {
+                let environment = Environment::new_enclosed(Rc::clone(&self.environment));
+                self.execute_block(statements, environment)
+            }
+            Statement::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                if is_truthy(&self.evaluate(condition)?) {
+                    self.execute(then_branch)
+                } else if let Some(else_branch) = else_branch {
+                    self.execute(else_branch)
+                } else {
+                    Ok(Flow::Normal)
+                }
+            }
+            Statement::While {
+                condition,
+                body,
+                increment,
+            } => self.execute_while(condition, body, increment),
+            Statement::ForIn {
+                variable,
+                keyword,
+                iterable,
+                body,
+            } => self.execute_for_in(variable, keyword, iterable, body),
+            Statement::Break => Ok(Flow::Break),
+            Statement::Continue => Ok(Flow::Continue),
+            Statement::Throw { keyword, value } => {
+                let value = self.evaluate(value)?;
+                let msg = match &value {
+                    LiteralType::String { lit } => lit.clone(),
+                    v => self.stringify(v)?,
+                };
+                Err(InterpreterError {
+                    tok: keyword.clone(),
+                    msg,
+                    thrown: Some(Box::new(value)),
+                })
+            }
+            Statement::Try {
+                body,
+                catch,
+                finally,
+            } => self.execute_try(body, catch, finally),
+            Statement::Class {
+                name,
+                traits,
+                methods,
+            } => {
+                let class = self.class(name, traits, methods)?;
+                self.environment.borrow_mut().define(
+                    name.lexeme,
+                    LiteralType::Class {
+                        lit: Rc::new(class),
+                    },
+                );
+                Ok(Flow::Normal)
+            }
+            Statement::Trait { name, methods } => {
+                let members = self.members(methods);
+                self.environment.borrow_mut().define(
+                    name.lexeme,
+                    LiteralType::Trait {
+                        lit: Rc::new(Trait {
+                            name: name.lexeme,
+                            members,
+                        }),
+                    },
+                );
+                Ok(Flow::Normal)
+            }
+            Statement::Import { path, name } => {
+                let module = self.import_module(path)?;
+                self.environment
+                    .borrow_mut()
+                    .define(name.lexeme, LiteralType::Map { lit: module });
+                Ok(Flow::Normal)
+            }
+            Statement::FromImport { path, names } => {
+                let map = self.import_module(path)?;
+                for name in names {
+                    let key = MapKey::String(name.lexeme.to_string());
+                    let value = match map.borrow().get(&key) {
+                        Some(value) => value.clone(),
+                        None => {
+                            return Err(InterpreterError {
+                                tok: name.clone(),
+                                msg: format!(
+                                    "Module {} has no member '{}'.",
+                                    path.lexeme, name.lexeme
+                                ),
+                                thrown: None,
+                            })
+                        }
+                    };
+                    self.environment.borrow_mut().define(name.lexeme, value);
+                }
+                Ok(Flow::Normal)
+            }
+        }
+    }
+
+    /// The members a class or trait body declares, closing over the
+    /// environment the declaration runs in.
+    fn members(&self, methods: &[Method<'src>]) -> Vec<Member<'src>> {
+        methods
+            .iter()
+            .map(|method| Member {
+                name: method.name.lexeme,
+                kind: method.kind,
+                class_level: method.class_level,
+                function: Rc::new(Function {
+                    declaration: Rc::clone(&method.lambda),
+                    closure: Rc::clone(&self.environment),
+                    file: self.file.clone(),
+                }),
+            })
+            .collect()
+    }
+
+    /// Makes the class a `class` statement declares, with a metaclass for
+    /// its `class` members. It takes on the members of its traits that it
+    /// doesn't declare itself; two traits providing a member it doesn't
+    /// declare is an error.
+    fn class(
+        &self,
+        name: &Token<'src>,
+        traits: &[Token<'src>],
+        methods: &[Method<'src>],
+    ) -> Result<Class<'src>, InterpreterError<'src>> {
+        let mut members = self.members(methods);
+        let declared = members.len();
+        let mut provided_by = Vec::new();
+        for trait_name in traits {
+            let value = self.environment.borrow().get(trait_name)?;
+            let class_trait = match value {
+                LiteralType::Trait { lit } => lit,
+                _ => {
+                    return Err(InterpreterError {
+                        tok: trait_name.clone(),
+                        msg: format!("'{}' is not a trait.", trait_name.lexeme),
+                        thrown: None,
+                    })
+                }
+            };
+            for member in &class_trait.members {
+                let same =
+                    |m: &Member<'src>| m.name == member.name && m.class_level == member.class_level;
+                if members[..declared].iter().any(same) {
+                    continue;
+                }
+                let earlier = members[declared..]
+                    .iter()
+                    .zip(&provided_by)
+                    .find(|(m, t)| same(m) && !Rc::ptr_eq(t, &class_trait));
+                if let Some((_, other)) = earlier {
+                    return Err(InterpreterError {
+                        tok: name.clone(),
+                        msg: trait_conflict(name.lexeme, member.name, other.name, class_trait.name),
+                        thrown: None,
+                    });
+                }
+                members.push(member.clone());
+                provided_by.push(Rc::clone(&class_trait));
+            }
+        }
+
+        let mut instance_members = Methods::default();
+        let mut class_members = Methods::default();
+        for member in members {
+            let level = match member.class_level {
+                true => &mut class_members,
+                false => &mut instance_members,
+            };
+            level.insert(member.kind, member.name, member.function);
+        }
+        let metaclass = Class {
+            name: name.lexeme,
+            methods: class_members,
+            metaclass: None,
+        };
+        Ok(Class {
+            name: name.lexeme,
+            methods: instance_members,
+            metaclass: Some(Rc::new(metaclass)),
+        })
+    }
+
+    /// Runs a program in the global scope. Returns the value of its last
+    /// statement if that is an expression statement, for the caller to
+    /// print. A top-level `return` ends the program early.
+    pub fn run(
+        &mut self,
+        statements: &[Statement<'src>],
+    ) -> Result<Option<LiteralType<'src>>, InterpreterError<'src>> {
+        let (body, last) = match statements.split_last() {
+            Some((Statement::Expression { expr }, body)) => (body, Some(expr)),
+            _ => (statements, None),
+        };
+        let globals = Rc::clone(&self.environment);
+        if let Flow::Return { .. } = self.execute_statements(body, globals)? {
+            return Ok(None);
+        }
+        match last {
+            Some(expr) => Ok(Some(self.evaluate(expr)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the namespace of the module `path` names, running it the
+    /// first time it is imported. A module is a list of statements run in a
+    /// scope of its own under fresh globals; its namespace is a frozen map
+    /// from the names defined in that scope to their values. A top-level
+    /// `return` ends the module early.
+    fn import_module(
+        &mut self,
+        path: &Token<'src>,
+    ) -> Result<Rc<RefCell<Map<'src>>>, InterpreterError<'src>> {
+        let error = |msg: String| InterpreterError {
+            tok: path.clone(),
+            msg,
+            thrown: None,
+        };
+        let name = match &path.literal {
+            Some(Literal::String { lit }) => lit,
+            _ => panic!("import_module: module path without a string literal"),
+        };
+        let file = match modules::resolve(name, self.file.as_deref().map(|p| p.as_path())) {
+            Some(file) => file,
+            None => return Err(error(format!("Could not find module {}.", path.lexeme))),
+        };
+        if let Some(i) = self.importing.iter().position(|p| *p == file) {
+            let cycle = modules::describe_cycle(&self.importing[i..], &file);
+            return Err(error(format!("Import cycle: {}.", cycle)));
+        }
+        if let Some(module) = self.modules.get(&file) {
+            return Ok(Rc::clone(module));
+        }
+
+        let source = match modules::read_source(&file) {
+            Some(source) => source,
+            None => return Err(error(format!("Could not read module {}.", path.lexeme))),
+        };
+        let statements = match Parser::new(new_scanner(source)).parse_module() {
+            Ok(statements) => statements,
+            Err(_) => return Err(error(format!("Could not parse module {}.", path.lexeme))),
+        };
+        if Resolver::new().resolve(&statements).is_err() {
+            return Err(error(format!("Could not parse module {}.", path.lexeme)));
+        }
+
+        let globals = Rc::new(RefCell::new(self.globals()));
+        let scope = Rc::new(RefCell::new(Environment::new_enclosed(globals)));
+        let previous_file = self.file.replace(Rc::new(file.clone()));
+        self.importing.push(file.clone());
+        let result = self.execute_statements(&statements, Rc::clone(&scope));
+        self.importing.pop();
+        self.file = previous_file;
+        result?;
+
+        let mut namespace = Map::new();
+        for (name, value) in scope.borrow().values() {
+            let key = MapKey::String(name.to_string());
+            let lit = LiteralType::String {
+                lit: name.to_string(),
+            };
+            namespace.insert(key, lit, value);
+        }
+        // Every importer shares the cached namespace, so none may change it.
+        namespace.freeze();
+        let module = Rc::new(RefCell::new(namespace));
+        self.modules.insert(file, Rc::clone(&module));
+        Ok(module)
+    }
+
+    /// The catch clause handles any error from the try block, thrown or
+    /// raised by the interpreter. The finally block then runs however the
+    /// try and catch blocks finished; if it finishes abruptly itself, by
+    /// returning, breaking or throwing, that replaces the pending outcome.
+    fn execute_try(
+        &mut self,
+        body: &[Statement<'src>],
+        catch: &Option<(Token<'src>, Vec<Statement<'src>>)>,
+        finally: &Option<Vec<Statement<'src>>>,
+    ) -> Result<Flow<'src>, InterpreterError<'src>> {
+        let environment = Environment::new_enclosed(Rc::clone(&self.environment));
+        let mut result = self.execute_block(body, environment);
+        if let (Err(error), Some((name, handler))) = (&result, catch) {
+            let mut environment = Environment::new_enclosed(Rc::clone(&self.environment));
+            environment.define(name.lexeme, error_value(error));
+            result = self.execute_block(handler, environment);
+        }
+        if let Some(finally) = finally {
+            let environment = Environment::new_enclosed(Rc::clone(&self.environment));
+            match self.execute_block(finally, environment)? {
+                Flow::Normal => {}
+                flow => return Ok(flow),
+            }
+        }
+        result
+    }
+
+    /// Runs statements in `environment`, stopping at the first one that
+    /// doesn't complete normally.
+    fn execute_block(
+        &mut self,
+        statements: &[Statement<'src>],
+        environment: Environment<'src>,
+    ) -> Result<Flow<'src>, InterpreterError<'src>> {
+        self.execute_statements(statements, Rc::new(RefCell::new(environment)))
+    }
+
+    /// Runs statements in `environment` until one doesn't complete normally.
+    fn execute_statements(
+        &mut self,
+        statements: &[Statement<'src>],
+        environment: Rc<RefCell<Environment<'src>>>,
+    ) -> Result<Flow<'src>, InterpreterError<'src>> {
+        let previous = std::mem::replace(&mut self.environment, environment);
+        let mut result = Ok(Flow::Normal);
+        for statement in statements {
+            result = self.execute(statement);
+            if !matches!(result, Ok(Flow::Normal)) {
+                break;
+            }
+        }
+        self.environment = previous;
+        result
+    }
+
+    /// `break` leaves the loop and `continue` ends the current pass through
+    /// the body. Either way the blocks they are nested in have already been
+    /// exited. The increment of a `for` loop runs after every pass that
+    /// doesn't break out of the loop.
+    fn execute_while(
+        &mut self,
+        condition: &Expression<'src>,
+        body: &Statement<'src>,
+        increment: &Option<Expression<'src>>,
+    ) -> Result<Flow<'src>, InterpreterError<'src>> {
+        while is_truthy(&self.evaluate(condition)?) {
+            match self.execute(body)? {
+                Flow::Break => break,
+                Flow::Return { value } => return Ok(Flow::Return { value }),
+                Flow::Normal | Flow::Continue => {}
+            }
+            if let Some(increment) = increment {
+                self.evaluate(increment)?;
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    /// Each iteration runs the body in a fresh environment holding the loop
+    /// variable, so closures created in the body capture that iteration's
+    /// value.
+    fn execute_for_in(
+        &mut self,
+        variable: &Token<'src>,
+        keyword: &Token<'src>,
+        iterable: &Expression<'src>,
+        body: &Statement<'src>,
+    ) -> Result<Flow<'src>, InterpreterError<'src>> {
+        let iterable = self.evaluate(iterable)?;
+        let mut values = LoopValues::new(iterable, keyword, self)?;
+        while let Some(value) = values.next_value(keyword, self)? {
+            let mut environment = Environment::new_enclosed(Rc::clone(&self.environment));
+            environment.define(variable.lexeme, value);
+            match self.execute_block(std::slice::from_ref(body), environment)? {
+                Flow::Break => break,
+                Flow::Return { value } => return Ok(Flow::Return { value }),
+                Flow::Normal | Flow::Continue => {}
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    pub fn evaluate(
+        &mut self,
+        expr: &Expression<'src>,
+    ) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+        match expr {
+            Expression::Binary {
+                left,
+                operator,
+                right,
+            } => self.evaluate_binary(left, right, operator),
+            Expression::Unary { operator, right } => self.evaluate_unary(operator, right),
+            Expression::Grouping { group } => self.evaluate(group),
+            Expression::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                // Only the chosen branch is evaluated.
+                let condition = self.evaluate(condition)?;
+                if is_truthy(&condition) {
+                    self.evaluate(then_branch)
+                } else {
+                    self.evaluate(else_branch)
+                }
+            }
+            Expression::Variable { name } => self.environment.borrow().get(name),
+            Expression::Assign {
+                name,
+                operator,
+                value,
+            } => self.evaluate_assign(name, operator, value),
+            Expression::Call {
+                callee,
+                paren,
+                arguments,
+                named,
+            } => self.evaluate_call(callee, paren, arguments, named),
+            Expression::Lambda { lambda } => Ok(LiteralType::Function {
+                lit: Rc::new(Function {
+                    declaration: Rc::clone(lambda),
+                    closure: Rc::clone(&self.environment),
+                    file: self.file.clone(),
+                }),
+            }),
+            Expression::Destructure {
+                target,
+                equals,
+                value,
+            } => {
+                let value = self.evaluate(value)?;
+                self.bind(target, equals, value.clone(), false)?;
+                Ok(value)
+            }
+            Expression::List { elements, .. } => {
+                let mut values = Vec::new();
+                for e in elements {
+                    values.push(self.evaluate(e)?);
+                }
+                Ok(LiteralType::List {
+                    lit: Rc::new(RefCell::new(List::new(values))),
+                })
+            }
+            Expression::Map { brace, entries } => {
+                let mut map = Map::new();
+                for (k, v) in entries {
+                    let key = self.evaluate(k)?;
+                    let value = self.evaluate(v)?;
+                    map.insert(MapKey::new(&key, brace)?, key, value);
+                }
+                Ok(LiteralType::Map {
+                    lit: Rc::new(RefCell::new(map)),
+                })
+            }
+            Expression::Index {
+                object,
+                bracket,
+                index,
+            } => self.evaluate_index(object, bracket, index),
+            Expression::SetIndex {
+                object,
+                bracket,
+                index,
+                operator,
+                value,
+            } => self.evaluate_set_index(object, bracket, index, operator, value),
+            Expression::Slice {
+                object,
+                bracket,
+                start,
+                end,
+            } => self.evaluate_slice(object, bracket, start, end),
+            Expression::Match {
+                keyword,
+                subject,
+                arms,
+            } => self.evaluate_match(keyword, subject, arms),
+            Expression::Property { object, name } => {
+                let object = self.evaluate(object)?;
+                self.get_property(object, name)
+            }
+            Expression::SetProperty {
+                object,
+                name,
+                operator,
+                value,
+            } => {
+                let object = self.evaluate(object)?;
+                let value = self.evaluate(value)?;
+                self.store_property(object, name, operator, value)
+            }
+            Expression::This { keyword } => self.environment.borrow().get(keyword),
+            Expression::Literal { lit } => Ok(literal_value(lit)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs `source` as a program, returning its printed value or the
+    /// message of the error that ended it.
+    fn run(source: &str) -> Result<String, String> {
+        let statements = Parser::new(new_scanner(source))
+            .parse()
+            .map_err(|e| e.to_string())?;
+        let mut interpreter = Interpreter::new(false, None, Vec::new());
+        match interpreter.run(&statements) {
+            Ok(value) => Ok(value.map(|v| stringify(&v)).unwrap_or_default()),
+            Err(e) => Err(e.msg),
+        }
+    }
+
+    const F: &str = "var f = fun (a, b = 2, c = a + b) { return [a, b, c]; };\n";
+
+    #[test]
+    fn named_arguments_fill_parameters_by_name() {
+        assert_eq!(
+            run(&format!("{}f(1, c: 5)", F)),
+            Ok("[1, 2, 5]".to_string())
+        );
+        assert_eq!(
+            run(&format!("{}f(b: 3, a: 1)", F)),
+            Ok("[1, 3, 4]".to_string())
+        );
+    }
+
+    #[test]
+    fn defaults_can_refer_to_earlier_parameters() {
+        assert_eq!(run(&format!("{}f(1)", F)), Ok("[1, 2, 3]".to_string()));
+        assert_eq!(
+            run(&format!("{}f(1, 10)", F)),
+            Ok("[1, 10, 11]".to_string())
+        );
+    }
+
+    #[test]
+    fn duplicate_named_argument_is_an_error() {
+        assert_eq!(
+            run(&format!("{}f(1, a: 2)", F)),
+            Err("Got multiple values for parameter 'a'.".to_string())
+        );
+        assert_eq!(
+            run(&format!("{}f(1, b: 2, b: 3)", F)),
+            Err("Got multiple values for parameter 'b'.".to_string())
+        );
+    }
+
+    #[test]
+    fn unknown_named_argument_is_an_error() {
+        assert_eq!(
+            run(&format!("{}f(1, d: 2)", F)),
+            Err("Unknown parameter 'd'.".to_string())
+        );
+    }
+
+    #[test]
+    fn missing_required_argument_is_an_error() {
+        assert_eq!(
+            run(&format!("{}f(b: 1)", F)),
+            Err("Missing argument for parameter 'a'.".to_string())
+        );
+        assert_eq!(
+            run(&format!("{}f()", F)),
+            Err("Expected 1 to 3 arguments but got 0.".to_string())
+        );
+    }
+
+    #[test]
+    fn named_arguments_are_not_counted_against_positional_ones() {
+        // Three arguments for three parameters, but one names a parameter
+        // the positional arguments already filled.
+        assert_eq!(
+            run(&format!("{}f(1, 2, a: 3)", F)),
+            Err("Got multiple values for parameter 'a'.".to_string())
+        );
+        assert_eq!(
+            run(&format!("{}f(1, 2, 3, 4)", F)),
+            Err("Expected 1 to 3 arguments but got 4.".to_string())
+        );
+    }
+
+    const RECT: &str = "class Rect {
+        init(w, h) { this.w = w; this.h = h; }
+        area { return this.w * this.h; }
+        set side(s) { this.w = s; this.h = s; }
+        class square(s) { return this(s, s); }
+    }
+";
+
+    #[test]
+    fn getters_and_setters_run_on_property_access() {
+        assert_eq!(
+            run(&format!(
+                "{}var r = Rect(2, 3); r.side = 4; [r.w, r.area]",
+                RECT
+            )),
+            Ok("[4, 16]".to_string())
+        );
+        assert_eq!(
+            run(&format!("{}var r = Rect(2, 3); r.w += 1; r.area", RECT)),
+            Ok("9".to_string())
+        );
+    }
+
+    #[test]
+    fn class_methods_are_called_on_the_class() {
+        assert_eq!(
+            run(&format!("{}Rect.square(5).area", RECT)),
+            Ok("25".to_string())
+        );
+        assert_eq!(
+            run(&format!("{}Rect(1, 1).square(5)", RECT)),
+            Err("Undefined property 'square'.".to_string())
+        );
+    }
+
+    #[test]
+    fn for_in_uses_the_iterator_protocol_of_instances() {
+        let source = "class Upto {
+            init(n) { this.n = n; }
+            iter() { this.i = 0; return this; }
+            next() {
+                if (this.i == this.n) return StopIteration;
+                this.i += 1;
+                return this.i;
+            }
+        }
+        var seen = [];
+        for (x in Upto(3)) { push(seen, x); }
+        seen";
+        assert_eq!(run(source), Ok("[1, 2, 3]".to_string()));
+    }
+
+    const MONEY: &str = "class Money {
+        init(cents) { this.cents = cents; }
+        __add__(other) { return Money(this.cents + other.cents); }
+        __eq__(other) { return this.cents == other.cents; }
+        __lt__(other) { return this.cents < other.cents; }
+        __str__() { return \"money\"; }
+    }
+";
+
+    #[test]
+    fn operators_dispatch_to_special_methods() {
+        assert_eq!(
+            run(&format!("{}(Money(1) + Money(2)).cents", MONEY)),
+            Ok("3".to_string())
+        );
+        assert_eq!(
+            run(&format!(
+                "{}var a = Money(1); var b = Money(2);
+                [a == Money(1), a != b, a < b, a > b, a <= Money(1), b >= a]",
+                MONEY
+            )),
+            Ok("[true, true, true, false, true, true]".to_string())
+        );
+    }
+
+    #[test]
+    fn instances_print_through_str() {
+        let source = format!("{}[Money(1)]", MONEY);
+        let statements = Parser::new(new_scanner(&source)).parse().unwrap();
+        let mut interpreter = Interpreter::new(false, None, Vec::new());
+        let value = interpreter.run(&statements).unwrap().unwrap();
+        assert_eq!(interpreter.stringify(&value).unwrap(), "[money]");
+    }
+
+    #[test]
+    fn classes_take_on_trait_members() {
+        let source = "trait Greets {
+            greet() { return \"hi \" + this.name; }
+            class kind() { return \"greeter\"; }
+        }
+        class Person with Greets {
+            init(name) { this.name = name; }
+        }
+        [Person(\"ann\").greet(), Person.kind()]";
+        assert_eq!(run(source), Ok("[\"hi ann\", \"greeter\"]".to_string()));
+    }
+
+    #[test]
+    fn conflicting_traits_must_be_resolved_by_the_class() {
+        let traits = "trait A { f() { return 1; } }
+        trait B { f() { return 2; } }
+        ";
+        assert_eq!(
+            run(&format!(
+                "{}class C with A, B {{ f() {{ return 3; }} }} C().f()",
+                traits
+            )),
+            Ok("3".to_string())
+        );
+        // Only the runtime check can see which trait a parameter holds.
+        assert_eq!(
+            run(&format!(
+                "{}var make = fun (t) {{ class C with A, t {{}} return C; }}; make(B)",
+                traits
+            )),
+            Err("Class 'C' must declare 'f', which traits 'A' and 'B' both provide.".to_string())
+        );
+    }
 }
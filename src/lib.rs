@@ -1,24 +1,35 @@
+#![allow(clippy::needless_return)]
+
 use std::fs;
 use std::io;
 use std::io::Write;
+use std::path::Path;
 
-use crate::interpreter::{evaluate, InterpreterError};
+use crate::interpreter::{Interpreter, InterpreterError};
 use crate::parser::Parser;
+use crate::resolver::Resolver;
 use crate::scanner::new_scanner;
 
+mod bigint;
+mod class;
+mod environment;
 mod interpreter;
+mod list;
+mod map;
+mod modules;
+mod natives;
 mod parser;
+mod range;
+mod resolver;
 mod scanner;
 mod token;
 
 fn is_alpha(c: char) -> bool {
-    let b = c.is_ascii_alphabetic() || (c == '_');
-    b
+    c.is_ascii_alphabetic() || (c == '_')
 }
 
 fn is_alphanumeric(c: char) -> bool {
-    let an = is_alpha(c) || c.is_ascii_digit();
-    an
+    is_alpha(c) || c.is_ascii_digit()
 }
 
 fn error(line: i64, message: String) {
@@ -30,51 +41,46 @@ fn report(line: i64, location: String, message: String) {
     eprintln!("[line {}] Error{}: {}", line, location, message);
 }
 
-fn run(_s: String) {
-    let mut scanner = new_scanner(_s);
-    let tokens = scanner.scan_tokens();
-    //for t in &tokens {
-    //    println!("{}", t);
-    //}
-    let mut parser = Parser::new(tokens);
-    let r = parser.parse();
-    match r {
-        Ok(r) => {
-            if let Some(r) = r {
-                //println!("Parsed: {}", r);
-                let result = evaluate(r);
-                match result {
-                    Ok(l) => {
-                        let s = match l {
-                            parser::LiteralType::Float { lit } => {
-                                format!("{}", lit)
-                            }
-                            parser::LiteralType::String { lit } => {
-                                format!("\"{}\"", lit)
-                            }
-                            parser::LiteralType::Bool { lit } => {
-                                format!("{}", lit)
-                            }
-                            parser::LiteralType::Nil { lit } => {
-                                format!("nil")
-                            }
-                        };
-                        println!("{}", s)
-                    }
-                    Err(InterpreterError { tok, msg }) => println!("{}\n[line {}]", msg, tok.line),
-                }
-            }
-        }
+/// Settings chosen on the command line.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Options {
+    pub strict_arithmetic: bool,
+}
+
+/// Runs `source` with `interpreter`, printing the program's value or the
+/// error that ended it.
+fn run<'src>(source: &'src str, interpreter: &mut Interpreter<'src>) {
+    let scanner = new_scanner(source);
+    let mut parser = Parser::new(scanner);
+    let statements = match parser.parse() {
+        Ok(statements) => statements,
         Err(_) => return,
+    };
+    if Resolver::new().resolve(&statements).is_err() {
+        return;
+    }
+    let printed = interpreter
+        .run(&statements)
+        .and_then(|value| value.map(|l| interpreter.stringify(&l)).transpose());
+    match printed {
+        Ok(Some(text)) => println!("{}", text),
+        Ok(None) => {}
+        Err(InterpreterError { tok, msg, .. }) => println!("{}\n[line {}]", msg, tok.line),
     }
 }
 
-pub fn run_file(path: String) -> io::Result<()> {
-    let contents = fs::read_to_string(path)?;
-    Ok(run(contents))
+/// `args` are the command line arguments given after the script path.
+pub fn run_file(path: String, args: Vec<String>, options: Options) -> io::Result<()> {
+    let contents = fs::read_to_string(&path)?;
+    let mut interpreter = Interpreter::new(options.strict_arithmetic, Some(Path::new(&path)), args);
+    run(&contents, &mut interpreter);
+    Ok(())
 }
 
-pub fn run_prompt() -> io::Result<()> {
+/// Reads and runs a line at a time. Definitions carry over from one line to
+/// the next, so each line's source is kept for the rest of the session.
+pub fn run_prompt(options: Options) -> io::Result<()> {
+    let mut interpreter = Interpreter::new(options.strict_arithmetic, None, Vec::new());
     loop {
         let mut buffer = String::new();
         print!("> ");
@@ -83,7 +89,7 @@ pub fn run_prompt() -> io::Result<()> {
         if read_len == 0 {
             break;
         } else {
-            run(buffer.clone());
+            run(Box::leak(buffer.into_boxed_str()), &mut interpreter);
         }
     }
     Ok(())
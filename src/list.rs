@@ -0,0 +1,55 @@
+use std::ops::{Deref, DerefMut};
+
+use crate::interpreter::InterpreterError;
+use crate::parser::LiteralType;
+use crate::token::Token;
+
+/// The contents of a list value. It derefs to its elements; code that
+/// changes them calls `check_mutable` first.
+#[derive(Debug, Default)]
+pub struct List<'src> {
+    elements: Vec<LiteralType<'src>>,
+    frozen: bool,
+}
+
+impl<'src> List<'src> {
+    pub fn new(elements: Vec<LiteralType<'src>>) -> List<'src> {
+        List {
+            elements,
+            frozen: false,
+        }
+    }
+
+    pub fn freeze(&mut self) {
+        self.frozen = true;
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    pub fn check_mutable(&self, tok: &Token<'src>) -> Result<(), InterpreterError<'src>> {
+        if self.frozen {
+            return Err(InterpreterError {
+                tok: tok.clone(),
+                msg: "Can't modify a frozen list.".to_string(),
+                thrown: None,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl<'src> Deref for List<'src> {
+    type Target = Vec<LiteralType<'src>>;
+
+    fn deref(&self) -> &Vec<LiteralType<'src>> {
+        &self.elements
+    }
+}
+
+impl<'src> DerefMut for List<'src> {
+    fn deref_mut(&mut self) -> &mut Vec<LiteralType<'src>> {
+        &mut self.elements
+    }
+}
@@ -1,14 +1,24 @@
 use std::io;
 
+fn usage() -> ! {
+    println!("Usage: rlox [--strict-arithmetic] [script [args...]]");
+    std::process::exit(64)
+}
+
 fn main() -> io::Result<()> {
-    let args: Vec<_> = std::env::args().collect();
-    if args.len() > 2 {
-        println!("Usage: rlox [script]");
-        std::process::exit(64)
-    } else if args.len() == 2 {
-        rlox::run_file(args[1].to_string())?;
-    } else {
-        rlox::run_prompt()?;
+    let mut args = std::env::args().skip(1).peekable();
+    let mut options = rlox::Options::default();
+    while let Some(flag) = args.next_if(|a| a.starts_with("--")) {
+        match flag.as_str() {
+            "--strict-arithmetic" => options.strict_arithmetic = true,
+            _ => usage(),
+        }
+    }
+    // Anything after the script path belongs to the script, which sees it
+    // as the list `args`.
+    match args.next() {
+        Some(path) => rlox::run_file(path, args.collect(), options)?,
+        None => rlox::run_prompt(options)?,
     }
     Ok(())
 }
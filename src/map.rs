@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+
+use crate::bigint::BigInt;
+use crate::interpreter::InterpreterError;
+use crate::parser::LiteralType;
+use crate::token::Token;
+
+/// The hashable form of a map key. Keys that `==` treats as equal hash to
+/// the same `MapKey`: every integral number becomes an `Integer` when it
+/// fits in an i64 and a `BigInt` otherwise, so `1`, `1.0` and `1n` (and
+/// `0.0` and `-0.0`) are all one key.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MapKey {
+    Nil,
+    Bool(bool),
+    Integer(i64),
+    BigInt(BigInt),
+    Float(u64),
+    String(String),
+}
+
+impl MapKey {
+    /// Only strings, numbers, booleans and nil can be keys, and NaN can't
+    /// since it isn't equal to itself.
+    pub fn new<'src>(
+        value: &LiteralType<'src>,
+        tok: &Token<'src>,
+    ) -> Result<MapKey, InterpreterError<'src>> {
+        let key = match value {
+            LiteralType::Nil => MapKey::Nil,
+            LiteralType::Bool { lit } => MapKey::Bool(*lit),
+            LiteralType::Integer { lit } => MapKey::Integer(*lit),
+            LiteralType::BigInt { lit } => match lit.to_i64() {
+                Some(i) => MapKey::Integer(i),
+                None => MapKey::BigInt(lit.clone()),
+            },
+            LiteralType::Float { lit } if lit.is_nan() => {
+                return Err(InterpreterError {
+                    tok: tok.clone(),
+                    msg: "Map key can't be NaN.".to_string(),
+                    thrown: None,
+                })
+            }
+            LiteralType::Float { lit } if lit.fract() == 0.0 => {
+                if *lit >= i64::MIN as f64 && *lit < i64::MAX as f64 {
+                    MapKey::Integer(*lit as i64)
+                } else {
+                    MapKey::BigInt(BigInt::from_f64(*lit).unwrap())
+                }
+            }
+            LiteralType::Float { lit } => MapKey::Float(lit.to_bits()),
+            LiteralType::String { lit } => MapKey::String(lit.clone()),
+            _ => {
+                return Err(InterpreterError {
+                    tok: tok.clone(),
+                    msg: "Map keys must be strings, numbers, booleans or nil.".to_string(),
+                    thrown: None,
+                })
+            }
+        };
+        Ok(key)
+    }
+}
+
+/// An insertion-ordered hash map from Lox values to Lox values. Each entry
+/// keeps the key as it was first inserted, which is what iteration yields.
+/// Code that changes a map on behalf of a program calls `check_mutable`
+/// first.
+#[derive(Debug, Default)]
+pub struct Map<'src> {
+    entries: Vec<(LiteralType<'src>, LiteralType<'src>)>,
+    index: HashMap<MapKey, usize>,
+    frozen: bool,
+}
+
+impl<'src> Map<'src> {
+    pub fn new() -> Map<'src> {
+        Map::default()
+    }
+
+    pub fn freeze(&mut self) {
+        self.frozen = true;
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    pub fn check_mutable(&self, tok: &Token<'src>) -> Result<(), InterpreterError<'src>> {
+        if self.frozen {
+            return Err(InterpreterError {
+                tok: tok.clone(),
+                msg: "Can't modify a frozen map.".to_string(),
+                thrown: None,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn contains(&self, key: &MapKey) -> bool {
+        self.index.contains_key(key)
+    }
+
+    pub fn get(&self, key: &MapKey) -> Option<&LiteralType<'src>> {
+        self.index.get(key).map(|i| &self.entries[*i].1)
+    }
+
+    /// Adds a new entry at the end, or replaces the value of an existing
+    /// one, keeping its position and original key.
+    pub fn insert(&mut self, key: MapKey, lit: LiteralType<'src>, value: LiteralType<'src>) {
+        match self.index.get(&key) {
+            Some(i) => self.entries[*i].1 = value,
+            None => {
+                self.index.insert(key, self.entries.len());
+                self.entries.push((lit, value));
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: &MapKey) -> Option<LiteralType<'src>> {
+        let i = self.index.remove(key)?;
+        let (_, value) = self.entries.remove(i);
+        for position in self.index.values_mut() {
+            if *position > i {
+                *position -= 1;
+            }
+        }
+        Some(value)
+    }
+
+    pub fn keys(&self) -> Vec<LiteralType<'src>> {
+        self.entries.iter().map(|(k, _)| k.clone()).collect()
+    }
+
+    pub fn entries(&self) -> &[(LiteralType<'src>, LiteralType<'src>)] {
+        &self.entries
+    }
+}
@@ -0,0 +1,40 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Finds the file an import names. A relative path is tried against the
+/// directory of the importing file first, or the working directory for the
+/// REPL, and then against each directory listed in `RLOX_PATH`. The result
+/// is canonical, so the same file always has the same cache entry.
+pub fn resolve(path: &str, importer: Option<&Path>) -> Option<PathBuf> {
+    let path = Path::new(path);
+    if path.is_absolute() {
+        return path.canonicalize().ok();
+    }
+
+    let mut dirs = vec![match importer.and_then(|p| p.parent()) {
+        Some(dir) => dir.to_path_buf(),
+        None => PathBuf::from("."),
+    }];
+    if let Some(search) = env::var_os("RLOX_PATH") {
+        dirs.extend(env::split_paths(&search));
+    }
+    dirs.iter()
+        .map(|dir| dir.join(path))
+        .find(|candidate| candidate.is_file())
+        .and_then(|candidate| candidate.canonicalize().ok())
+}
+
+/// Reads a module's source. Modules stay loaded for the rest of the run and
+/// their tokens borrow from the source, so it is leaked rather than owned.
+pub fn read_source(path: &Path) -> Option<&'static str> {
+    let contents = fs::read_to_string(path).ok()?;
+    Some(Box::leak(contents.into_boxed_str()))
+}
+
+/// Formats an import cycle as `a.lox -> b.lox -> a.lox`.
+pub fn describe_cycle(cycle: &[PathBuf], repeated: &Path) -> String {
+    let mut names: Vec<String> = cycle.iter().map(|p| p.display().to_string()).collect();
+    names.push(repeated.display().to_string());
+    names.join(" -> ")
+}
@@ -0,0 +1,338 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::bigint::BigInt;
+use crate::environment::Environment;
+use crate::interpreter::{missing_key, to_integer, InterpreterError};
+use crate::list::List;
+use crate::map::{Map, MapKey};
+use crate::parser::LiteralType;
+use crate::range::Range;
+use crate::token::Token;
+
+/// A function implemented in Rust. `paren` is the call's closing paren, used
+/// to locate runtime errors.
+type NativeFn<'src> =
+    fn(&Token<'src>, Vec<LiteralType<'src>>) -> Result<LiteralType<'src>, InterpreterError<'src>>;
+
+#[derive(Clone, Copy)]
+pub struct NativeFunction<'src> {
+    pub name: &'static str,
+    pub min_arity: usize,
+    pub max_arity: usize,
+    pub function: NativeFn<'src>,
+}
+
+impl fmt::Debug for NativeFunction<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+pub fn define_natives(environment: &mut Environment) {
+    let natives = [
+        NativeFunction {
+            name: "len",
+            min_arity: 1,
+            max_arity: 1,
+            function: len,
+        },
+        NativeFunction {
+            name: "push",
+            min_arity: 2,
+            max_arity: 2,
+            function: push,
+        },
+        NativeFunction {
+            name: "pop",
+            min_arity: 1,
+            max_arity: 1,
+            function: pop,
+        },
+        NativeFunction {
+            name: "insert",
+            min_arity: 3,
+            max_arity: 3,
+            function: insert,
+        },
+        NativeFunction {
+            name: "remove",
+            min_arity: 2,
+            max_arity: 2,
+            function: remove,
+        },
+        NativeFunction {
+            name: "has",
+            min_arity: 2,
+            max_arity: 2,
+            function: has,
+        },
+        NativeFunction {
+            name: "keys",
+            min_arity: 1,
+            max_arity: 1,
+            function: keys,
+        },
+        NativeFunction {
+            name: "range",
+            min_arity: 2,
+            max_arity: 3,
+            function: range,
+        },
+        NativeFunction {
+            name: "freeze",
+            min_arity: 1,
+            max_arity: 1,
+            function: freeze,
+        },
+        NativeFunction {
+            name: "isFrozen",
+            min_arity: 1,
+            max_arity: 1,
+            function: is_frozen,
+        },
+    ];
+    for native in natives {
+        environment.define(native.name, LiteralType::Native { lit: native });
+    }
+}
+
+fn error<'src>(paren: &Token<'src>, msg: String) -> InterpreterError<'src> {
+    InterpreterError {
+        tok: paren.clone(),
+        msg,
+        thrown: None,
+    }
+}
+
+fn expect_list<'src>(
+    paren: &Token<'src>,
+    name: &str,
+    value: &LiteralType<'src>,
+) -> Result<Rc<RefCell<List<'src>>>, InterpreterError<'src>> {
+    match value {
+        LiteralType::List { lit } => Ok(Rc::clone(lit)),
+        _ => Err(error(
+            paren,
+            format!("First argument to '{}' must be a list.", name),
+        )),
+    }
+}
+
+fn expect_map<'src>(
+    paren: &Token<'src>,
+    name: &str,
+    value: &LiteralType<'src>,
+) -> Result<Rc<RefCell<Map<'src>>>, InterpreterError<'src>> {
+    match value {
+        LiteralType::Map { lit } => Ok(Rc::clone(lit)),
+        _ => Err(error(
+            paren,
+            format!("First argument to '{}' must be a map.", name),
+        )),
+    }
+}
+
+/// Resolves a possibly negative list index against `len`, counting negative
+/// indices back from the end. `None` when it falls outside `0..len`.
+pub fn list_index(index: i64, len: usize) -> Option<usize> {
+    let len = len as i64;
+    let i = if index < 0 { index + len } else { index };
+    if (0..len).contains(&i) {
+        Some(i as usize)
+    } else {
+        None
+    }
+}
+
+fn len<'src>(
+    paren: &Token<'src>,
+    args: Vec<LiteralType<'src>>,
+) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+    let n = match &args[0] {
+        LiteralType::List { lit } => lit.borrow().len(),
+        LiteralType::String { lit } => lit.chars().count(),
+        LiteralType::Map { lit } => lit.borrow().len(),
+        LiteralType::Range { lit } => {
+            return Ok(match i64::try_from(lit.len()) {
+                Ok(n) => LiteralType::Integer { lit: n },
+                Err(_) => LiteralType::BigInt {
+                    lit: BigInt::from_u64(lit.len()),
+                },
+            })
+        }
+        _ => {
+            return Err(error(
+                paren,
+                "Argument to 'len' must be a list, a map, a range or a string.".to_string(),
+            ))
+        }
+    };
+    Ok(LiteralType::Integer { lit: n as i64 })
+}
+
+fn push<'src>(
+    paren: &Token<'src>,
+    mut args: Vec<LiteralType<'src>>,
+) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+    let list = expect_list(paren, "push", &args[0])?;
+    let value = args.remove(1);
+    list.borrow().check_mutable(paren)?;
+    list.borrow_mut().push(value);
+    Ok(LiteralType::Nil)
+}
+
+fn pop<'src>(
+    paren: &Token<'src>,
+    args: Vec<LiteralType<'src>>,
+) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+    let list = expect_list(paren, "pop", &args[0])?;
+    list.borrow().check_mutable(paren)?;
+    let popped = list.borrow_mut().pop();
+    match popped {
+        Some(v) => Ok(v),
+        None => Err(error(paren, "Can't pop from an empty list.".to_string())),
+    }
+}
+
+/// Inserts before the element at the index, so an index equal to the length
+/// appends.
+fn insert<'src>(
+    paren: &Token<'src>,
+    mut args: Vec<LiteralType<'src>>,
+) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+    let list = expect_list(paren, "insert", &args[0])?;
+    list.borrow().check_mutable(paren)?;
+    let index = to_integer(&args[1], paren, "List index must be an integer.")?;
+    let value = args.remove(2);
+    let len = list.borrow().len();
+    let position = if index == len as i64 {
+        Some(len)
+    } else {
+        list_index(index, len)
+    };
+    match position {
+        Some(i) => {
+            list.borrow_mut().insert(i, value);
+            Ok(LiteralType::Nil)
+        }
+        None => Err(error(
+            paren,
+            format!("List index {} out of range for length {}.", index, len),
+        )),
+    }
+}
+
+/// Removes a list element by index or a map entry by key, and returns the
+/// removed value.
+fn remove<'src>(
+    paren: &Token<'src>,
+    args: Vec<LiteralType<'src>>,
+) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+    if let LiteralType::Map { lit } = &args[0] {
+        let key = MapKey::new(&args[1], paren)?;
+        lit.borrow().check_mutable(paren)?;
+        let removed = lit.borrow_mut().remove(&key);
+        return match removed {
+            Some(v) => Ok(v),
+            None => Err(missing_key(paren, &args[1])),
+        };
+    }
+    let list = match &args[0] {
+        LiteralType::List { lit } => Rc::clone(lit),
+        _ => {
+            return Err(error(
+                paren,
+                "First argument to 'remove' must be a list or a map.".to_string(),
+            ))
+        }
+    };
+    list.borrow().check_mutable(paren)?;
+    let index = to_integer(&args[1], paren, "List index must be an integer.")?;
+    let len = list.borrow().len();
+    match list_index(index, len) {
+        Some(i) => Ok(list.borrow_mut().remove(i)),
+        None => Err(error(
+            paren,
+            format!("List index {} out of range for length {}.", index, len),
+        )),
+    }
+}
+
+fn has<'src>(
+    paren: &Token<'src>,
+    args: Vec<LiteralType<'src>>,
+) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+    let map = expect_map(paren, "has", &args[0])?;
+    let key = MapKey::new(&args[1], paren)?;
+    let found = map.borrow().contains(&key);
+    Ok(LiteralType::Bool { lit: found })
+}
+
+/// Returns a new list of the map's keys in insertion order.
+fn keys<'src>(
+    paren: &Token<'src>,
+    args: Vec<LiteralType<'src>>,
+) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+    let map = expect_map(paren, "keys", &args[0])?;
+    let keys = map.borrow().keys();
+    Ok(LiteralType::List {
+        lit: Rc::new(RefCell::new(List::new(keys))),
+    })
+}
+
+/// `range(start, end, step)` returns a range of the integers from `start` up
+/// to but not including `end`, counting down when `step` is negative. The
+/// step defaults to 1.
+fn range<'src>(
+    paren: &Token<'src>,
+    args: Vec<LiteralType<'src>>,
+) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+    let msg = "Arguments to 'range' must be integers.";
+    let start = to_integer(&args[0], paren, msg)?;
+    let end = to_integer(&args[1], paren, msg)?;
+    let step = match args.get(2) {
+        Some(step) => to_integer(step, paren, msg)?,
+        None => 1,
+    };
+    if step == 0 {
+        return Err(error(paren, "Range step can't be zero.".to_string()));
+    }
+    Ok(LiteralType::Range {
+        lit: Range { start, end, step },
+    })
+}
+
+/// Makes a list or map read-only from now on and returns it. Freezing is
+/// shallow: the elements themselves can still change.
+fn freeze<'src>(
+    paren: &Token<'src>,
+    args: Vec<LiteralType<'src>>,
+) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+    match &args[0] {
+        LiteralType::List { lit } => lit.borrow_mut().freeze(),
+        LiteralType::Map { lit } => lit.borrow_mut().freeze(),
+        _ => {
+            return Err(error(
+                paren,
+                "Only lists and maps can be frozen.".to_string(),
+            ))
+        }
+    }
+    Ok(args[0].clone())
+}
+
+/// Whether `freeze` has been called on a list or map. Values of other
+/// types can't change anyway, so they count as frozen.
+fn is_frozen<'src>(
+    _paren: &Token<'src>,
+    args: Vec<LiteralType<'src>>,
+) -> Result<LiteralType<'src>, InterpreterError<'src>> {
+    let frozen = match &args[0] {
+        LiteralType::List { lit } => lit.borrow().is_frozen(),
+        LiteralType::Map { lit } => lit.borrow().is_frozen(),
+        _ => true,
+    };
+    Ok(LiteralType::Bool { lit: frozen })
+}
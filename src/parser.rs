@@ -1,55 +1,422 @@
-use crate::token::{Token, TokenType};
-use std::collections::VecDeque;
+use crate::bigint::BigInt;
+use crate::class::{Class, Instance, Trait};
+use crate::error;
+use crate::interpreter::{same_literal, Function};
+use crate::list::List;
+use crate::map::Map;
+use crate::natives::NativeFunction;
+use crate::range::Range;
+use crate::scanner::Scanner;
+use crate::token::{Literal, Token, TokenType};
+use std::cell::RefCell;
+use std::rc::Rc;
 use std::{error::Error, fmt};
 
 #[derive(Clone, Debug)]
-pub enum LiteralType {
+pub enum LiteralType<'src> {
     Bool { lit: bool },
+    Integer { lit: i64 },
+    BigInt { lit: BigInt },
     Float { lit: f64 },
     String { lit: String },
-    Nil { lit: bool },
+    Function { lit: Rc<Function<'src>> },
+    Native { lit: NativeFunction<'src> },
+    List { lit: Rc<RefCell<List<'src>>> },
+    Map { lit: Rc<RefCell<Map<'src>>> },
+    Range { lit: Range },
+    Class { lit: Rc<Class<'src>> },
+    Trait { lit: Rc<Trait<'src>> },
+    Instance { lit: Rc<RefCell<Instance<'src>>> },
+    Nil,
 }
 
-impl fmt::Display for LiteralType {
+impl fmt::Display for LiteralType<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match &*self {
+        match self {
             LiteralType::Bool { lit } => {
                 write!(f, "LiteralType - Bool: {}", lit)
             }
+            LiteralType::Integer { lit } => {
+                write!(f, "LiteralType - Integer: {}", lit)
+            }
+            LiteralType::BigInt { lit } => {
+                write!(f, "LiteralType - BigInt: {}", lit)
+            }
             LiteralType::Float { lit } => {
                 write!(f, "LiteralType - Float: {}", lit)
             }
             LiteralType::String { lit } => {
                 write!(f, "LiteralType - String: {}", lit)
             }
-            LiteralType::Nil { lit } => {
+            LiteralType::Function { .. } => {
+                write!(f, "LiteralType - Function")
+            }
+            LiteralType::Native { lit } => {
+                write!(f, "LiteralType - Native: {}", lit.name)
+            }
+            LiteralType::List { lit } => {
+                write!(f, "LiteralType - List of {}", lit.borrow().len())
+            }
+            LiteralType::Map { lit } => {
+                write!(f, "LiteralType - Map of {}", lit.borrow().len())
+            }
+            LiteralType::Range { lit } => {
+                write!(f, "LiteralType - Range of {}", lit.len())
+            }
+            LiteralType::Class { lit } => {
+                write!(f, "LiteralType - Class: {}", lit.name)
+            }
+            LiteralType::Trait { lit } => {
+                write!(f, "LiteralType - Trait: {}", lit.name)
+            }
+            LiteralType::Instance { lit } => {
+                write!(f, "LiteralType - Instance of {}", lit.borrow().class.name)
+            }
+            LiteralType::Nil => {
                 write!(f, "LiteralType - Nil")
             }
         }
     }
 }
 
-pub enum Expression {
+pub enum Expression<'src> {
     Binary {
-        left: Box<Expression>,
-        operator: Token,
-        right: Box<Expression>,
+        left: Box<Expression<'src>>,
+        operator: Token<'src>,
+        right: Box<Expression<'src>>,
     },
     Unary {
-        operator: Token,
-        right: Box<Expression>,
+        operator: Token<'src>,
+        right: Box<Expression<'src>>,
     },
     Literal {
-        lit: Token,
+        lit: Literal,
     },
     Grouping {
-        group: Box<Expression>,
+        group: Box<Expression<'src>>,
+    },
+    Conditional {
+        condition: Box<Expression<'src>>,
+        then_branch: Box<Expression<'src>>,
+        else_branch: Box<Expression<'src>>,
+    },
+    Variable {
+        name: Token<'src>,
+    },
+    Assign {
+        name: Token<'src>,
+        operator: Token<'src>,
+        value: Box<Expression<'src>>,
+    },
+    /// `named` holds the `name: value` arguments, which come after the
+    /// positional ones.
+    Call {
+        callee: Box<Expression<'src>>,
+        paren: Token<'src>,
+        arguments: Vec<Expression<'src>>,
+        named: Vec<(Token<'src>, Expression<'src>)>,
+    },
+    Lambda {
+        lambda: Rc<Lambda<'src>>,
+    },
+    Match {
+        keyword: Token<'src>,
+        subject: Box<Expression<'src>>,
+        arms: Vec<MatchArm<'src>>,
+    },
+    List {
+        bracket: Token<'src>,
+        elements: Vec<Expression<'src>>,
+    },
+    Map {
+        brace: Token<'src>,
+        entries: Vec<(Expression<'src>, Expression<'src>)>,
+    },
+    Index {
+        object: Box<Expression<'src>>,
+        bracket: Token<'src>,
+        index: Box<Expression<'src>>,
+    },
+    /// `[a, b] = value`, assigning the parts of a value to several targets.
+    Destructure {
+        target: Target<'src>,
+        equals: Token<'src>,
+        value: Box<Expression<'src>>,
+    },
+    SetIndex {
+        object: Box<Expression<'src>>,
+        bracket: Token<'src>,
+        index: Box<Expression<'src>>,
+        operator: Token<'src>,
+        value: Box<Expression<'src>>,
+    },
+    Slice {
+        object: Box<Expression<'src>>,
+        bracket: Token<'src>,
+        start: Option<Box<Expression<'src>>>,
+        end: Option<Box<Expression<'src>>>,
+    },
+    /// `object.name` reads a field, runs a getter or binds a method.
+    Property {
+        object: Box<Expression<'src>>,
+        name: Token<'src>,
+    },
+    /// `object.name = value` runs a setter or stores a field.
+    SetProperty {
+        object: Box<Expression<'src>>,
+        name: Token<'src>,
+        operator: Token<'src>,
+        value: Box<Expression<'src>>,
+    },
+    This {
+        keyword: Token<'src>,
+    },
+}
+
+/// The parameters and body of an anonymous function. The arrow form
+/// `(a) => a + 1` is parsed as a body holding a single return statement.
+/// `rest` is the `...name` parameter that collects surplus arguments.
+pub struct Lambda<'src> {
+    pub params: Vec<Parameter<'src>>,
+    pub rest: Option<Token<'src>>,
+    pub body: Vec<Statement<'src>>,
+}
+
+/// A parameter and its default value, which is evaluated at call time in
+/// the callee's scope when no argument is passed for it.
+pub struct Parameter<'src> {
+    pub name: Token<'src>,
+    pub default: Option<Expression<'src>>,
+}
+
+/// A member of a class body. `class_level` is set for members declared
+/// with a `class` prefix, which belong to the class object itself.
+pub struct Method<'src> {
+    pub name: Token<'src>,
+    pub kind: MethodKind,
+    pub class_level: bool,
+    pub lambda: Rc<Lambda<'src>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MethodKind {
+    /// `name(params) { body }`.
+    Method,
+    /// `name { body }`, run when the property is read.
+    Getter,
+    /// `set name(value) { body }`, run when the property is assigned.
+    Setter,
+}
+
+/// Something a value can be stored into, by assignment or by a `var`
+/// declaration. Declarations only use names, lists and fields.
+pub enum Target<'src> {
+    Variable {
+        name: Token<'src>,
+    },
+    Index {
+        object: Box<Expression<'src>>,
+        bracket: Token<'src>,
+        index: Box<Expression<'src>>,
+    },
+    Property {
+        object: Box<Expression<'src>>,
+        name: Token<'src>,
+    },
+    /// `[a, b, ...rest]` takes a list's elements in order. Without a rest
+    /// name the list must have exactly as many elements as there are
+    /// targets.
+    List {
+        bracket: Token<'src>,
+        elements: Vec<Target<'src>>,
+        rest: Option<Token<'src>>,
+    },
+    /// `{x, y}` takes the map entries with those names as keys.
+    Fields {
+        brace: Token<'src>,
+        names: Vec<Token<'src>>,
+    },
+}
+
+impl fmt::Display for Target<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Target::Variable { name } => write!(f, "{}", name.lexeme),
+            Target::Index { object, index, .. } => write!(f, "(index {} {})", *object, *index),
+            Target::Property { object, name } => write!(f, "(. {} {})", *object, name.lexeme),
+            Target::List { elements, rest, .. } => {
+                write!(f, "[")?;
+                for (i, e) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", e)?;
+                }
+                if let Some(rest) = rest {
+                    write!(f, " ...{}", rest.lexeme)?;
+                }
+                write!(f, "]")
+            }
+            Target::Fields { names, .. } => {
+                let names: Vec<&str> = names.iter().map(|n| n.lexeme).collect();
+                write!(f, "{{{}}}", names.join(" "))
+            }
+        }
+    }
+}
+
+/// One `pattern if guard => body` arm of a match expression.
+pub struct MatchArm<'src> {
+    pub pattern: Pattern<'src>,
+    pub guard: Option<Expression<'src>>,
+    pub body: Expression<'src>,
+}
+
+pub enum Pattern<'src> {
+    /// `_` matches anything without binding it.
+    Wildcard,
+    Literal {
+        lit: Literal,
+    },
+    /// A name matches anything and binds the value to it.
+    Binding {
+        name: Token<'src>,
+    },
+    /// `[p1, p2]` matches a list of exactly that length, element by element.
+    List {
+        elements: Vec<Pattern<'src>>,
+    },
+    /// `p1 | p2` matches if any alternative does.
+    Alternatives {
+        alternatives: Vec<Pattern<'src>>,
+    },
+}
+
+impl<'src> Pattern<'src> {
+    /// Whether the pattern matches every value.
+    fn is_irrefutable(&self) -> bool {
+        match self {
+            Pattern::Wildcard | Pattern::Binding { .. } => true,
+            Pattern::Literal { .. } | Pattern::List { .. } => false,
+            Pattern::Alternatives { alternatives } => {
+                alternatives.iter().any(|p| p.is_irrefutable())
+            }
+        }
+    }
+
+    /// The first name the pattern binds, if any.
+    fn binding(&self) -> Option<&Token<'src>> {
+        match self {
+            Pattern::Binding { name } => Some(name),
+            Pattern::Wildcard | Pattern::Literal { .. } => None,
+            Pattern::List { elements: patterns }
+            | Pattern::Alternatives {
+                alternatives: patterns,
+            } => patterns.iter().find_map(|p| p.binding()),
+        }
+    }
+
+    /// The literals a pattern made only of literals matches, or `None` if it
+    /// has any other kind of pattern.
+    fn literals(&self) -> Option<Vec<&Literal>> {
+        match self {
+            Pattern::Literal { lit } => Some(vec![lit]),
+            Pattern::Alternatives { alternatives } => {
+                let mut lits = Vec::new();
+                for p in alternatives {
+                    lits.extend(p.literals()?);
+                }
+                Some(lits)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Statements make up programs, modules and blocks, including function
+/// bodies.
+pub enum Statement<'src> {
+    Expression {
+        expr: Expression<'src>,
+    },
+    Return {
+        value: Option<Expression<'src>>,
+    },
+    /// `var target = value;` defines the names in the target. A plain name
+    /// may leave out the value, which then defaults to nil.
+    Var {
+        target: Target<'src>,
+        value: Option<(Token<'src>, Expression<'src>)>,
+    },
+    /// `const name = value;` defines a name that can't be assigned to.
+    Const {
+        name: Token<'src>,
+        value: Expression<'src>,
+    },
+    Block {
+        statements: Vec<Statement<'src>>,
+    },
+    If {
+        condition: Expression<'src>,
+        then_branch: Box<Statement<'src>>,
+        else_branch: Option<Box<Statement<'src>>>,
+    },
+    /// `while (condition) body`. A `for` loop is parsed into a block holding
+    /// its initializer and a while loop with an `increment`, which runs
+    /// after each pass through the body, including one ended by `continue`.
+    While {
+        condition: Expression<'src>,
+        body: Box<Statement<'src>>,
+        increment: Option<Expression<'src>>,
+    },
+    /// `for (variable in iterable) body`. `keyword` is the `in` token, which
+    /// locates errors about values that can't be iterated.
+    ForIn {
+        variable: Token<'src>,
+        keyword: Token<'src>,
+        iterable: Expression<'src>,
+        body: Box<Statement<'src>>,
+    },
+    Break,
+    Continue,
+    Throw {
+        keyword: Token<'src>,
+        value: Expression<'src>,
+    },
+    /// `import "path" as name;` binds the module's namespace to `name`.
+    Import {
+        path: Token<'src>,
+        name: Token<'src>,
+    },
+    /// `from "path" import a, b;` binds names the module defines.
+    FromImport {
+        path: Token<'src>,
+        names: Vec<Token<'src>>,
+    },
+    /// `try { body } catch (name) { ... } finally { ... }`, where at least
+    /// one of the two clauses is present.
+    Try {
+        body: Vec<Statement<'src>>,
+        catch: Option<(Token<'src>, Vec<Statement<'src>>)>,
+        finally: Option<Vec<Statement<'src>>>,
+    },
+    /// `class Name with A, B { members }`, where the `with` clause names
+    /// the traits whose members the class takes on.
+    Class {
+        name: Token<'src>,
+        traits: Vec<Token<'src>>,
+        methods: Vec<Method<'src>>,
+    },
+    /// `trait Name { members }`, with members written as in a class.
+    Trait {
+        name: Token<'src>,
+        methods: Vec<Method<'src>>,
     },
 }
 
-impl fmt::Display for Expression {
+impl fmt::Display for Expression<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match &*self {
+        match self {
             Expression::Binary {
                 left,
                 operator,
@@ -66,17 +433,130 @@ impl fmt::Display for Expression {
             Expression::Grouping { group } => {
                 write!(f, "(group {})", *group)
             }
+            Expression::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                write!(f, "(?: {} {} {})", *condition, *then_branch, *else_branch)
+            }
+            Expression::Variable { name } => {
+                write!(f, "{}", name.lexeme)
+            }
+            Expression::Assign {
+                name,
+                operator,
+                value,
+            } => {
+                write!(f, "({} {} {})", operator.lexeme, name.lexeme, *value)
+            }
+            Expression::Call {
+                callee,
+                arguments,
+                named,
+                ..
+            } => {
+                write!(f, "(call {}", *callee)?;
+                for a in arguments {
+                    write!(f, " {}", a)?;
+                }
+                for (name, value) in named {
+                    write!(f, " {}: {}", name.lexeme, value)?;
+                }
+                write!(f, ")")
+            }
+            Expression::Match { subject, arms, .. } => {
+                write!(f, "(match {} {} arms)", *subject, arms.len())
+            }
+            Expression::Lambda { lambda } => {
+                let mut params: Vec<String> = lambda
+                    .params
+                    .iter()
+                    .map(|p| p.name.lexeme.to_string())
+                    .collect();
+                if let Some(rest) = &lambda.rest {
+                    params.push(format!("...{}", rest.lexeme));
+                }
+                write!(f, "(fun ({}))", params.join(" "))
+            }
+            Expression::Destructure { target, value, .. } => {
+                write!(f, "(= {} {})", target, *value)
+            }
+            Expression::List { elements, .. } => {
+                write!(f, "(list")?;
+                for e in elements {
+                    write!(f, " {}", e)?;
+                }
+                write!(f, ")")
+            }
+            Expression::Map { entries, .. } => {
+                write!(f, "(map")?;
+                for (k, v) in entries {
+                    write!(f, " ({} {})", k, v)?;
+                }
+                write!(f, ")")
+            }
+            Expression::Index { object, index, .. } => {
+                write!(f, "(index {} {})", *object, *index)
+            }
+            Expression::SetIndex {
+                object,
+                index,
+                operator,
+                value,
+                ..
+            } => {
+                write!(
+                    f,
+                    "({} (index {} {}) {})",
+                    operator.lexeme, *object, *index, *value
+                )
+            }
+            Expression::Slice {
+                object, start, end, ..
+            } => {
+                write!(f, "(slice {}", *object)?;
+                for bound in [start, end] {
+                    match bound {
+                        Some(b) => write!(f, " {}", b)?,
+                        None => write!(f, " nil")?,
+                    }
+                }
+                write!(f, ")")
+            }
+            Expression::Property { object, name } => {
+                write!(f, "(. {} {})", *object, name.lexeme)
+            }
+            Expression::SetProperty {
+                object,
+                name,
+                operator,
+                value,
+            } => {
+                write!(
+                    f,
+                    "({} (. {} {}) {})",
+                    operator.lexeme, *object, name.lexeme, *value
+                )
+            }
+            Expression::This { .. } => write!(f, "this"),
         }
     }
 }
 
-enum Operator {
-    Minus,
-}
-
-pub struct Parser {
-    tokens: VecDeque<Token>,
+pub struct Parser<'src> {
+    scanner: Scanner<'src>,
+    current: Token<'src>,
     had_error: bool,
+    // First error reported by an error production. Those errors are printed
+    // as they are found and parsing carries on, but the parse still fails.
+    production_error: Option<ParseError>,
+    // Number of loops enclosing the statement being parsed, within the
+    // innermost function body.
+    loop_depth: usize,
+    // Whether the source is a program rather than a module, so an
+    // expression statement ending it needs no ';'.
+    program: bool,
 }
 
 #[derive(Debug)]
@@ -93,33 +573,65 @@ impl fmt::Display for ParseError {
     }
 }
 
-impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Parser {
+impl<'src> Parser<'src> {
+    pub fn new(mut scanner: Scanner<'src>) -> Parser<'src> {
+        let mut had_error = false;
+        let current = Parser::next_token(&mut scanner, &mut had_error);
         Parser {
-            tokens: VecDeque::from(tokens),
-            had_error: false,
+            scanner,
+            current,
+            had_error,
+            production_error: None,
+            loop_depth: 0,
+            program: false,
         }
     }
-    fn is_at_end(&self) -> bool {
-        if let Some(t) = self.tokens.front() {
-            return t.tok_type == TokenType::EOF;
-        } else {
-            return false;
+
+    /// Pulls the next token from the scanner, reporting and skipping any
+    /// lexical errors along the way.
+    fn next_token(scanner: &mut Scanner<'src>, had_error: &mut bool) -> Token<'src> {
+        loop {
+            match scanner.next() {
+                Some(Ok(t)) => return t,
+                Some(Err(e)) => {
+                    *had_error = true;
+                    error(e.line, e.msg);
+                }
+                None => panic!("next_token - scanner exhausted"),
+            }
         }
     }
 
+    fn is_at_end(&self) -> bool {
+        self.current.tok_type == TokenType::EOF
+    }
+
     fn check(&self, tt: TokenType) -> bool {
         if self.is_at_end() {
             return false;
         }
-        if let Some(t) = self.tokens.front() {
-            return t.tok_type == tt;
-        } else {
-            return false;
+        return self.current.tok_type == tt;
+    }
+
+    /// Whether the `n`th token after `current` has type `tt`. The scanner is
+    /// cloned so nothing is consumed; scan errors are left to be reported
+    /// when the tokens are actually reached.
+    fn check_ahead(&self, n: usize, tt: TokenType) -> bool {
+        let mut scanner = self.scanner.clone();
+        let mut tok_type = self.current.tok_type;
+        for _ in 0..n {
+            if tok_type == TokenType::EOF {
+                return false;
+            }
+            tok_type = match scanner.next() {
+                Some(Ok(t)) => t.tok_type,
+                _ => return false,
+            };
         }
+        tok_type == tt
     }
 
-    fn tok_match(&mut self, candidates: Vec<TokenType>) -> (bool, Option<Token>) {
+    fn tok_match(&mut self, candidates: Vec<TokenType>) -> (bool, Option<Token<'src>>) {
         let mut m = false;
         for c in candidates {
             m = self.check(c);
@@ -134,27 +646,147 @@ impl Parser {
         }
     }
 
-    pub fn parse(&mut self) -> Result<Option<Expression>, ParseError> {
-        if self.tokens.len() == 1 && self.tokens[0].tok_type == TokenType::EOF {
-            return Ok(None);
+    /// Parses a program: a list of statements like a module, except that the
+    /// last one may leave out its ';' if it is an expression, so a program
+    /// can be a single expression.
+    pub fn parse(&mut self) -> Result<Vec<Statement<'src>>, ParseError> {
+        self.program = true;
+        self.parse_module()
+    }
+
+    /// Parses a module, a list of statements.
+    pub fn parse_module(&mut self) -> Result<Vec<Statement<'src>>, ParseError> {
+        let mut statements = Vec::new();
+        while !self.is_at_end() {
+            match self.statement() {
+                Ok(statement) => statements.push(statement),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return Err(e);
+                }
+            }
+        }
+        match self.production_error.take() {
+            Some(e) => Err(e),
+            None => Ok(statements),
+        }
+    }
+
+    fn expression(&mut self) -> Result<Expression<'src>, ParseError> {
+        //println!("Parsing expression");
+        let expr = self.assignment()?;
+        Ok(expr)
+    }
+
+    fn assignment(&mut self) -> Result<Expression<'src>, ParseError> {
+        let expr = self.conditional()?;
+        let (m, t) = self.tok_match(vec![
+            TokenType::Equal,
+            TokenType::PlusEqual,
+            TokenType::MinusEqual,
+            TokenType::StarEqual,
+            TokenType::SlashEqual,
+            TokenType::PercentEqual,
+        ]);
+        if !m {
+            return Ok(expr);
+        }
+        match t {
+            Some(t) => {
+                let target = self.assignment_target(expr, &t)?;
+                let value = Box::new(self.assignment()?);
+                Ok(match target {
+                    Target::Variable { name } => Expression::Assign {
+                        name,
+                        operator: t,
+                        value,
+                    },
+                    Target::Index {
+                        object,
+                        bracket,
+                        index,
+                    } => Expression::SetIndex {
+                        object,
+                        bracket,
+                        index,
+                        operator: t,
+                        value,
+                    },
+                    Target::Property { object, name } => Expression::SetProperty {
+                        object,
+                        name,
+                        operator: t,
+                        value,
+                    },
+                    target => Expression::Destructure {
+                        target,
+                        equals: t,
+                        value,
+                    },
+                })
+            }
+            None => {
+                panic!("assignment: tok_match returned True and None")
+            }
         }
-        let expr = self.expression();
+    }
+
+    /// Checks that an expression parsed before `operator` can be assigned
+    /// to. Variables, list elements and properties can be, for = and the
+    /// compound operators alike; a list of targets can be with plain =.
+    fn assignment_target(
+        &mut self,
+        expr: Expression<'src>,
+        operator: &Token<'src>,
+    ) -> Result<Target<'src>, ParseError> {
         match expr {
-            Ok(expr) => Ok(Some(expr)),
-            Err(e) => {
-                eprintln!("{}", e);
-                Err(e)
+            Expression::Variable { name } => Ok(Target::Variable { name }),
+            Expression::Index {
+                object,
+                bracket,
+                index,
+            } => Ok(Target::Index {
+                object,
+                bracket,
+                index,
+            }),
+            Expression::Property { object, name } => Ok(Target::Property { object, name }),
+            Expression::List { bracket, elements } if operator.tok_type == TokenType::Equal => {
+                let mut targets = Vec::new();
+                for e in elements {
+                    targets.push(self.assignment_target(e, operator)?);
+                }
+                Ok(Target::List {
+                    bracket,
+                    elements: targets,
+                    rest: None,
+                })
             }
+            _ => Err(self.error(operator.clone(), "Invalid assignment target.".to_string())),
         }
     }
 
-    fn expression(&mut self) -> Result<Expression, ParseError> {
-        //println!("Parsing expression");
+    fn conditional(&mut self) -> Result<Expression<'src>, ParseError> {
         let expr = self.equality()?;
-        Ok(expr)
+        let (m, _) = self.tok_match(vec![TokenType::Question]);
+        if !m {
+            return Ok(expr);
+        }
+        let then_branch = self.expression()?;
+        self.consume(
+            TokenType::Colon,
+            "Expect ':' after then branch of conditional expression.".to_string(),
+        )?;
+        // Recursing makes a ? b : c ? d : e group as a ? b : (c ? d : e).
+        let else_branch = self.conditional()?;
+        Ok(Expression::Conditional {
+            condition: Box::new(expr),
+            then_branch: Box::new(then_branch),
+            else_branch: Box::new(else_branch),
+        })
     }
 
-    fn equality(&mut self) -> Result<Expression, ParseError> {
+    fn equality(&mut self) -> Result<Expression<'src>, ParseError> {
         //println!("Parsing equality");
         let mut expr = self.comparison()?;
 
@@ -183,79 +815,40 @@ impl Parser {
         Ok(expr)
     }
 
-    fn advance(&mut self) -> Option<Token> {
-        let t = &self.tokens[0];
-        if t.tok_type != TokenType::EOF {
-            return self.tokens.pop_front();
-        } else {
+    fn advance(&mut self) -> Option<Token<'src>> {
+        if self.is_at_end() {
             return None;
         }
+        let next = Parser::next_token(&mut self.scanner, &mut self.had_error);
+        return Some(std::mem::replace(&mut self.current, next));
+    }
+
+    /// Reports a problem that doesn't stop the program from running.
+    fn warning(&self, t: &Token<'src>, m: &str) {
+        eprintln!("{} at '{}' Warning: {}", t.line, t.lexeme, m);
     }
 
-    fn error(&mut self, t: Token, m: String) -> ParseError {
+    fn error(&mut self, t: Token<'src>, m: String) -> ParseError {
         self.had_error = true;
-        let msg: String;
-        if t.tok_type == TokenType::EOF {
-            msg = format!("at end {}", m);
+        let msg = if t.tok_type == TokenType::EOF {
+            format!("at end {}", m)
         } else {
-            msg = format!("at '{}' {}", t.lexeme, m)
-        }
+            format!("at '{}' {}", t.lexeme, m)
+        };
         ParseError { line: t.line, msg }
     }
 
-    fn synchronize(&mut self) {
-        while !self.is_at_end() {
-            let previous = self.advance();
-            let previous = match previous {
-                Some(previous) => previous,
-                None => {
-                    // This might be a parseerror??
-                    return;
-                }
-            };
-            if previous.tok_type == TokenType::Semicolon {
-                return;
-            }
-
-            if let Some(t) = self.tokens.front() {
-                if matches!(
-                    t.tok_type,
-                    TokenType::Class
-                        | TokenType::Fun
-                        | TokenType::Var
-                        | TokenType::For
-                        | TokenType::If
-                        | TokenType::While
-                        | TokenType::Print
-                        | TokenType::Return
-                ) {
-                    return;
-                }
-            } else {
-                return;
-            }
-        }
-    }
-
-    fn consume(&mut self, tt: TokenType, msg: String) -> Result<Option<Token>, ParseError> {
+    fn consume(&mut self, tt: TokenType, msg: String) -> Result<Option<Token<'src>>, ParseError> {
         if self.check(tt) {
             return Ok(self.advance());
         } else {
-            let t = self.tokens.front();
-            match t {
-                Some(t) => {
-                    let r = t.clone();
-                    let err = self.error(r, msg);
-                    return Err(err);
-                }
-                None => {
-                    panic!("consume - no tokens remaining")
-                }
-            }
+            let t = self.current.clone();
+            let err = self.error(t, msg);
+            return Err(err);
         }
     }
 
-    fn primary(&mut self) -> Result<Expression, ParseError> {
+    fn primary(&mut self) -> Result<Expression<'src>, ParseError> {
         //println!("Parsing primary");
         let (m, t) = self.tok_match(vec![
             TokenType::False,
@@ -268,24 +861,128 @@ impl Parser {
             match t {
                 Some(t) => {
                     //println!("Got {}", t);
-                    let b = Expression::Literal { lit: (t) };
-                    return Ok(b);
+                    return match t.literal {
+                        Some(lit) => Ok(Expression::Literal { lit }),
+                        None => Err(self.error(t, "Expect literal value.".to_string())),
+                    };
+                }
+                None => {
+                    panic!("primary: tok_match returned True and None")
                 }
+            }
+        }
+
+        let (m, t) = self.tok_match(vec![TokenType::Identifier]);
+        if m {
+            match t {
+                Some(name) => return Ok(Expression::Variable { name }),
                 None => {
                     panic!("primary: tok_match returned True and None")
                 }
             }
         }
 
+        let (m, t) = self.tok_match(vec![TokenType::This]);
+        if m {
+            match t {
+                Some(keyword) => return Ok(Expression::This { keyword }),
+                None => panic!("primary: tok_match returned True and None"),
+            }
+        }
+
+        let (m, t) = self.tok_match(vec![TokenType::Match]);
+        if m {
+            match t {
+                Some(keyword) => return self.match_expression(keyword),
+                None => panic!("primary: tok_match returned True and None"),
+            }
+        }
+
+        let (m, t) = self.tok_match(vec![TokenType::LeftBracket]);
+        if m {
+            let bracket = match t {
+                Some(t) => t,
+                None => panic!("primary: tok_match returned True and None"),
+            };
+            let mut elements = Vec::new();
+            if !self.check(TokenType::RightBracket) {
+                loop {
+                    elements.push(self.expression()?);
+                    let (m, _) = self.tok_match(vec![TokenType::Comma]);
+                    if !m {
+                        break;
+                    }
+                }
+            }
+            self.consume(
+                TokenType::RightBracket,
+                "Expect ']' after list elements.".to_string(),
+            )?;
+            return Ok(Expression::List { bracket, elements });
+        }
+
+        let (m, t) = self.tok_match(vec![TokenType::LeftBrace]);
+        if m {
+            let brace = match t {
+                Some(t) => t,
+                None => panic!("primary: tok_match returned True and None"),
+            };
+            let mut entries = Vec::new();
+            if !self.check(TokenType::RightBrace) {
+                loop {
+                    let key = self.expression()?;
+                    self.consume(TokenType::Colon, "Expect ':' after map key.".to_string())?;
+                    let value = self.expression()?;
+                    entries.push((key, value));
+                    let (m, _) = self.tok_match(vec![TokenType::Comma]);
+                    if !m {
+                        break;
+                    }
+                }
+            }
+            self.consume(
+                TokenType::RightBrace,
+                "Expect '}' after map entries.".to_string(),
+            )?;
+            return Ok(Expression::Map { brace, entries });
+        }
+
+        let (m, _) = self.tok_match(vec![TokenType::Fun]);
+        if m {
+            self.consume(TokenType::LeftParen, "Expect '(' after 'fun'.".to_string())?;
+            let (params, rest) = self.parameters(Vec::new())?;
+            let body = self.function_body()?;
+            return Ok(Expression::Lambda {
+                lambda: Rc::new(Lambda { params, rest, body }),
+            });
+        }
+
         let (m, t) = self.tok_match(vec![TokenType::LeftParen]);
         if m {
             match t {
                 Some(_) => {
+                    // An arrow function's parameter list looks like a
+                    // grouping until a comma or the => after it shows up.
+                    if self.check(TokenType::RightParen) || self.check(TokenType::DotDotDot) {
+                        let (params, rest) = self.parameters(Vec::new())?;
+                        return self.arrow_function(params, rest);
+                    }
+                    let start = self.current.clone();
                     let expr = self.expression()?;
+                    if self.check(TokenType::Comma) {
+                        let params = vec![self.arrow_parameter(expr, start)?];
+                        self.advance();
+                        let (params, rest) = self.parameters(params)?;
+                        return self.arrow_function(params, rest);
+                    }
                     self.consume(
                         TokenType::RightParen,
                         "Expect ')' after expression.".to_string(),
                     )?;
+                    if self.check(TokenType::Arrow) {
+                        let param = self.arrow_parameter(expr, start)?;
+                        return self.arrow_function(vec![param], None);
+                    }
                     return Ok(Expression::Grouping {
                         group: Box::new(expr),
                     });
@@ -295,47 +992,1061 @@ impl Parser {
                 }
             }
         }
-        if let Some(t) = self.tokens.front() {
-            Err(self.error(t.clone(), "Expect expression.".to_string()))
-        } else {
-            panic!("primary - no tokens remaining")
+        if let Some(right) = self.missing_left_operand()? {
+            return Ok(right);
         }
+
+        let t = self.current.clone();
+        Err(self.error(t, "Expect expression.".to_string()))
     }
 
-    fn unary(&mut self) -> Result<Expression, ParseError> {
-        //println!("parsing unary");
-        let (m, t) = self.tok_match(vec![TokenType::Bang, TokenType::Minus]);
-        if !m {
-            let p = self.primary()?;
-            return Ok(p);
+    /// Parses the rest of a parameter list, after any leading parameters
+    /// already in `params`, through the closing paren. Parameters with
+    /// defaults come after those without, and a `...rest` parameter comes
+    /// last.
+    fn parameters(
+        &mut self,
+        mut params: Vec<Parameter<'src>>,
+    ) -> Result<(Vec<Parameter<'src>>, Option<Token<'src>>), ParseError> {
+        let mut rest = None;
+        if !self.check(TokenType::RightParen) {
+            loop {
+                let (m, _) = self.tok_match(vec![TokenType::DotDotDot]);
+                if m {
+                    rest = Some(self.identifier("Expect rest parameter name after '...'.")?);
+                    if self.check(TokenType::Comma) {
+                        let t = self.current.clone();
+                        return Err(self.error(t, "Rest parameter must be last.".to_string()));
+                    }
+                    break;
+                }
+                let name = self.identifier("Expect parameter name.")?;
+                let mut default = None;
+                let (m, _) = self.tok_match(vec![TokenType::Equal]);
+                if m {
+                    default = Some(self.conditional()?);
+                }
+                params.push(Parameter { name, default });
+                let (m, _) = self.tok_match(vec![TokenType::Comma]);
+                if !m {
+                    break;
+                }
+            }
         }
-        match t {
-            Some(t) => {
-                let operator = t;
-                let right = self.unary()?;
-                return Ok(Expression::Unary {
-                    operator,
-                    right: Box::new(right),
-                });
+        self.consume(
+            TokenType::RightParen,
+            "Expect ')' after parameters.".to_string(),
+        )?;
+
+        let mut seen_default = false;
+        for p in &params {
+            if p.default.is_some() {
+                seen_default = true;
+            } else if seen_default {
+                let name = p.name.clone();
+                return Err(self.error(
+                    name,
+                    "Parameter without a default can't follow one with a default.".to_string(),
+                ));
             }
-            None => {
-                if let Some(t) = self.tokens.front() {
-                    let e = self.error(t.clone(), "Expected unary.".to_string());
-                    Err(e)
-                } else {
-                    panic!("unary - no tokens remaining")
+        }
+        Ok((params, rest))
+    }
+
+    /// Converts an expression parsed as part of a grouping into the
+    /// arrow function parameter it turned out to be: a name, or an
+    /// assignment `name = default`. `start` is the expression's first
+    /// token, where an invalid parameter is reported.
+    fn arrow_parameter(
+        &mut self,
+        expr: Expression<'src>,
+        start: Token<'src>,
+    ) -> Result<Parameter<'src>, ParseError> {
+        match expr {
+            Expression::Variable { name } => Ok(Parameter {
+                name,
+                default: None,
+            }),
+            Expression::Assign {
+                name,
+                operator,
+                value,
+            } if operator.tok_type == TokenType::Equal => Ok(Parameter {
+                name,
+                default: Some(*value),
+            }),
+            _ => Err(self.error(start, "Expect parameter name.".to_string())),
+        }
+    }
+
+    fn arrow_function(
+        &mut self,
+        params: Vec<Parameter<'src>>,
+        rest: Option<Token<'src>>,
+    ) -> Result<Expression<'src>, ParseError> {
+        self.consume(
+            TokenType::Arrow,
+            "Expect '=>' after arrow function parameters.".to_string(),
+        )?;
+        let value = self.expression()?;
+        let body = vec![Statement::Return { value: Some(value) }];
+        Ok(Expression::Lambda {
+            lambda: Rc::new(Lambda { params, rest, body }),
+        })
+    }
+
+    fn function_body(&mut self) -> Result<Vec<Statement<'src>>, ParseError> {
+        self.consume(
+            TokenType::LeftBrace,
+            "Expect '{' before function body.".to_string(),
+        )?;
+        // A loop around the function doesn't enclose statements in its body.
+        let loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+        let mut body = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            match self.statement() {
+                Ok(statement) => body.push(statement),
+                Err(e) => {
+                    self.loop_depth = loop_depth;
+                    return Err(e);
                 }
             }
         }
+        self.loop_depth = loop_depth;
+        self.consume(
+            TokenType::RightBrace,
+            "Expect '}' after function body.".to_string(),
+        )?;
+        Ok(body)
     }
 
-    fn factor(&mut self) -> Result<Expression, ParseError> {
-        let mut expr = self.unary()?;
-        loop {
-            let (m, t) = self.tok_match(vec![TokenType::Slash, TokenType::Star]);
-            if !m {
-                break;
-            }
+    fn statement(&mut self) -> Result<Statement<'src>, ParseError> {
+        let (m, t) = self.tok_match(vec![TokenType::Break, TokenType::Continue]);
+        if m {
+            if let Some(keyword) = t {
+                if self.loop_depth == 0 {
+                    let msg = format!("Can't use '{}' outside of a loop.", keyword.lexeme);
+                    return Err(self.error(keyword, msg));
+                }
+                let msg = format!("Expect ';' after '{}'.", keyword.lexeme);
+                self.consume(TokenType::Semicolon, msg)?;
+                return Ok(match keyword.tok_type {
+                    TokenType::Break => Statement::Break,
+                    _ => Statement::Continue,
+                });
+            }
+        }
+
+        let (m, _) = self.tok_match(vec![TokenType::If]);
+        if m {
+            return self.if_statement();
+        }
+
+        let (m, _) = self.tok_match(vec![TokenType::While]);
+        if m {
+            return self.while_statement();
+        }
+
+        let (m, _) = self.tok_match(vec![TokenType::For]);
+        if m {
+            if self.check(TokenType::LeftParen) && self.check_ahead(2, TokenType::In) {
+                return self.for_in_statement();
+            }
+            return self.for_statement();
+        }
+
+        let (m, _) = self.tok_match(vec![TokenType::Var]);
+        if m {
+            return self.var_declaration();
+        }
+
+        let (m, _) = self.tok_match(vec![TokenType::Const]);
+        if m {
+            let name = self.identifier("Expect constant name.")?;
+            self.consume(
+                TokenType::Equal,
+                "Expect '=' after constant name.".to_string(),
+            )?;
+            let value = self.expression()?;
+            self.consume(
+                TokenType::Semicolon,
+                "Expect ';' after constant declaration.".to_string(),
+            )?;
+            return Ok(Statement::Const { name, value });
+        }
+
+        let (m, t) = self.tok_match(vec![TokenType::Throw]);
+        if m {
+            let keyword = match t {
+                Some(t) => t,
+                None => panic!("statement: tok_match returned True and None"),
+            };
+            let value = self.expression()?;
+            self.consume(
+                TokenType::Semicolon,
+                "Expect ';' after thrown value.".to_string(),
+            )?;
+            return Ok(Statement::Throw { keyword, value });
+        }
+
+        let (m, _) = self.tok_match(vec![TokenType::Try]);
+        if m {
+            return self.try_statement();
+        }
+
+        let (m, _) = self.tok_match(vec![TokenType::Class]);
+        if m {
+            return self.class_declaration();
+        }
+
+        let (m, _) = self.tok_match(vec![TokenType::Trait]);
+        if m {
+            let name = self.identifier("Expect trait name.")?;
+            let methods = self.members("trait")?;
+            return Ok(Statement::Trait { name, methods });
+        }
+
+        let (m, _) = self.tok_match(vec![TokenType::Import]);
+        if m {
+            let path = self.module_path("import")?;
+            self.consume(TokenType::As, "Expect 'as' after module path.".to_string())?;
+            let name = self.identifier("Expect module name after 'as'.")?;
+            self.consume(TokenType::Semicolon, "Expect ';' after import.".to_string())?;
+            return Ok(Statement::Import { path, name });
+        }
+
+        let (m, _) = self.tok_match(vec![TokenType::From]);
+        if m {
+            let path = self.module_path("from")?;
+            self.consume(
+                TokenType::Import,
+                "Expect 'import' after module path.".to_string(),
+            )?;
+            let mut names = Vec::new();
+            loop {
+                names.push(self.identifier("Expect name to import.")?);
+                let (m, _) = self.tok_match(vec![TokenType::Comma]);
+                if !m {
+                    break;
+                }
+            }
+            self.consume(TokenType::Semicolon, "Expect ';' after import.".to_string())?;
+            return Ok(Statement::FromImport { path, names });
+        }
+
+        let (m, _) = self.tok_match(vec![TokenType::LeftBrace]);
+        if m {
+            return Ok(Statement::Block {
+                statements: self.block()?,
+            });
+        }
+
+        let (m, _) = self.tok_match(vec![TokenType::Return]);
+        if m {
+            let mut value = None;
+            if !self.check(TokenType::Semicolon) {
+                value = Some(self.expression()?);
+            }
+            self.consume(
+                TokenType::Semicolon,
+                "Expect ';' after return value.".to_string(),
+            )?;
+            return Ok(Statement::Return { value });
+        }
+
+        let expr = self.expression()?;
+        if !(self.program && self.is_at_end()) {
+            self.consume(
+                TokenType::Semicolon,
+                "Expect ';' after expression.".to_string(),
+            )?;
+        }
+        Ok(Statement::Expression { expr })
+    }
+
+    /// Parses the rest of `match (subject) { arms }` after the keyword. Arms
+    /// are separated by commas, and a trailing comma is allowed. An arm that
+    /// earlier arms already cover is reported with a warning.
+    fn match_expression(&mut self, keyword: Token<'src>) -> Result<Expression<'src>, ParseError> {
+        self.consume(
+            TokenType::LeftParen,
+            "Expect '(' after 'match'.".to_string(),
+        )?;
+        let subject = self.expression()?;
+        self.consume(
+            TokenType::RightParen,
+            "Expect ')' after match subject.".to_string(),
+        )?;
+        self.consume(
+            TokenType::LeftBrace,
+            "Expect '{' before match arms.".to_string(),
+        )?;
+
+        let mut arms = Vec::new();
+        // What the unguarded arms so far match: everything, or a set of
+        // literals.
+        let mut covered_all = false;
+        let mut covered: Vec<Literal> = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            let start = self.current.clone();
+            let pattern = self.pattern()?;
+            let mut guard = None;
+            let (m, _) = self.tok_match(vec![TokenType::If]);
+            if m {
+                guard = Some(self.expression()?);
+            }
+            self.consume(
+                TokenType::Arrow,
+                "Expect '=>' after match pattern.".to_string(),
+            )?;
+            let body = self.expression()?;
+
+            let unreachable = covered_all
+                || pattern.literals().is_some_and(|lits| {
+                    lits.iter()
+                        .all(|l| covered.iter().any(|c| same_literal(c, l)))
+                });
+            if unreachable {
+                self.warning(&start, "Unreachable match arm.");
+            }
+            if guard.is_none() {
+                covered_all |= pattern.is_irrefutable();
+                if let Some(lits) = pattern.literals() {
+                    covered.extend(lits.into_iter().cloned());
+                }
+            }
+            arms.push(MatchArm {
+                pattern,
+                guard,
+                body,
+            });
+
+            let (m, _) = self.tok_match(vec![TokenType::Comma]);
+            if !m {
+                break;
+            }
+        }
+        self.consume(
+            TokenType::RightBrace,
+            "Expect '}' after match arms.".to_string(),
+        )?;
+        Ok(Expression::Match {
+            keyword,
+            subject: Box::new(subject),
+            arms,
+        })
+    }
+
+    /// Alternatives can't bind variables, since only some of them would
+    /// bind each name.
+    fn pattern(&mut self) -> Result<Pattern<'src>, ParseError> {
+        let first = self.simple_pattern()?;
+        if !self.check(TokenType::Pipe) {
+            return Ok(first);
+        }
+        let mut alternatives = vec![first];
+        loop {
+            let (m, _) = self.tok_match(vec![TokenType::Pipe]);
+            if !m {
+                break;
+            }
+            alternatives.push(self.simple_pattern()?);
+        }
+        if let Some(name) = alternatives.iter().find_map(|p| p.binding()) {
+            let name = name.clone();
+            return Err(self.error(
+                name,
+                "Can't bind a variable in a pattern alternative.".to_string(),
+            ));
+        }
+        Ok(Pattern::Alternatives { alternatives })
+    }
+
+    fn simple_pattern(&mut self) -> Result<Pattern<'src>, ParseError> {
+        let (m, t) = self.tok_match(vec![
+            TokenType::False,
+            TokenType::True,
+            TokenType::Nil,
+            TokenType::Number,
+            TokenType::String,
+        ]);
+        if m {
+            match t.and_then(|t| t.literal) {
+                Some(lit) => return Ok(Pattern::Literal { lit }),
+                None => panic!("simple_pattern: literal token without a literal"),
+            }
+        }
+
+        let (m, t) = self.tok_match(vec![TokenType::Minus]);
+        if m {
+            let number = match self.consume(
+                TokenType::Number,
+                "Expect number after '-' in pattern.".to_string(),
+            )? {
+                Some(t) => t,
+                None => panic!("simple_pattern: consume returned Ok and None"),
+            };
+            let lit = match number.literal {
+                Some(Literal::Integer { lit }) => Literal::Integer { lit: -lit },
+                Some(Literal::BigInt { lit }) => Literal::BigInt { lit: -&lit },
+                Some(Literal::Float { lit }) => Literal::Float { lit: -lit },
+                _ => match t {
+                    Some(minus) => {
+                        return Err(
+                            self.error(minus, "Expect number after '-' in pattern.".to_string())
+                        )
+                    }
+                    None => panic!("simple_pattern: tok_match returned True and None"),
+                },
+            };
+            return Ok(Pattern::Literal { lit });
+        }
+
+        let (m, t) = self.tok_match(vec![TokenType::Identifier]);
+        if m {
+            return match t {
+                Some(name) if name.lexeme == "_" => Ok(Pattern::Wildcard),
+                Some(name) => Ok(Pattern::Binding { name }),
+                None => panic!("simple_pattern: tok_match returned True and None"),
+            };
+        }
+
+        let (m, _) = self.tok_match(vec![TokenType::LeftBracket]);
+        if m {
+            let mut elements = Vec::new();
+            if !self.check(TokenType::RightBracket) {
+                loop {
+                    elements.push(self.pattern()?);
+                    let (m, _) = self.tok_match(vec![TokenType::Comma]);
+                    if !m {
+                        break;
+                    }
+                }
+            }
+            self.consume(
+                TokenType::RightBracket,
+                "Expect ']' after list pattern.".to_string(),
+            )?;
+            return Ok(Pattern::List { elements });
+        }
+
+        let t = self.current.clone();
+        Err(self.error(t, "Expect pattern.".to_string()))
+    }
+
+    /// Parses the rest of a block after its `{`.
+    fn block(&mut self) -> Result<Vec<Statement<'src>>, ParseError> {
+        let mut statements = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            statements.push(self.statement()?);
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after block.".to_string())?;
+        Ok(statements)
+    }
+
+    fn if_statement(&mut self) -> Result<Statement<'src>, ParseError> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'if'.".to_string())?;
+        let condition = self.expression()?;
+        self.consume(
+            TokenType::RightParen,
+            "Expect ')' after if condition.".to_string(),
+        )?;
+        let then_branch = Box::new(self.statement()?);
+        let mut else_branch = None;
+        let (m, _) = self.tok_match(vec![TokenType::Else]);
+        if m {
+            else_branch = Some(Box::new(self.statement()?));
+        }
+        Ok(Statement::If {
+            condition,
+            then_branch,
+            else_branch,
+        })
+    }
+
+    fn while_statement(&mut self) -> Result<Statement<'src>, ParseError> {
+        self.consume(
+            TokenType::LeftParen,
+            "Expect '(' after 'while'.".to_string(),
+        )?;
+        let condition = self.expression()?;
+        self.consume(
+            TokenType::RightParen,
+            "Expect ')' after condition.".to_string(),
+        )?;
+        let body = self.loop_body()?;
+        Ok(Statement::While {
+            condition,
+            body,
+            increment: None,
+        })
+    }
+
+    /// `for (initializer; condition; increment) body`, where each clause may
+    /// be left out and the initializer may be a `var` declaration. It becomes
+    /// a block running the initializer once and then a while loop.
+    fn for_statement(&mut self) -> Result<Statement<'src>, ParseError> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.".to_string())?;
+        let mut initializer = None;
+        let (m, _) = self.tok_match(vec![TokenType::Semicolon]);
+        if !m {
+            let (m, _) = self.tok_match(vec![TokenType::Var]);
+            if m {
+                initializer = Some(self.var_declaration()?);
+            } else {
+                let expr = self.expression()?;
+                self.consume(
+                    TokenType::Semicolon,
+                    "Expect ';' after loop initializer.".to_string(),
+                )?;
+                initializer = Some(Statement::Expression { expr });
+            }
+        }
+
+        let condition = if self.check(TokenType::Semicolon) {
+            Expression::Literal {
+                lit: Literal::Bool { lit: true },
+            }
+        } else {
+            self.expression()?
+        };
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after loop condition.".to_string(),
+        )?;
+
+        let mut increment = None;
+        if !self.check(TokenType::RightParen) {
+            increment = Some(self.expression()?);
+        }
+        self.consume(
+            TokenType::RightParen,
+            "Expect ')' after for clauses.".to_string(),
+        )?;
+
+        let body = self.loop_body()?;
+        let mut statements: Vec<Statement<'src>> = initializer.into_iter().collect();
+        statements.push(Statement::While {
+            condition,
+            body,
+            increment,
+        });
+        Ok(Statement::Block { statements })
+    }
+
+    fn loop_body(&mut self) -> Result<Box<Statement<'src>>, ParseError> {
+        self.loop_depth += 1;
+        let body = self.statement();
+        self.loop_depth -= 1;
+        Ok(Box::new(body?))
+    }
+
+    fn var_declaration(&mut self) -> Result<Statement<'src>, ParseError> {
+        let target = self.declaration_target()?;
+        let mut value = None;
+        let (m, t) = self.tok_match(vec![TokenType::Equal]);
+        if m {
+            let equals = match t {
+                Some(t) => t,
+                None => panic!("var_declaration: tok_match returned True and None"),
+            };
+            value = Some((equals, self.expression()?));
+        } else if !matches!(target, Target::Variable { .. }) {
+            let t = self.current.clone();
+            return Err(self.error(t, "Expect '=' after destructuring pattern.".to_string()));
+        }
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after variable declaration.".to_string(),
+        )?;
+        Ok(Statement::Var { target, value })
+    }
+
+    /// Parses what a `var` declares: a name, `[a, [b, c], ...rest]` or
+    /// `{x, y}`.
+    fn declaration_target(&mut self) -> Result<Target<'src>, ParseError> {
+        let (m, t) = self.tok_match(vec![TokenType::LeftBracket]);
+        if m {
+            let bracket = match t {
+                Some(t) => t,
+                None => panic!("declaration_target: tok_match returned True and None"),
+            };
+            let mut elements = Vec::new();
+            let mut rest = None;
+            if !self.check(TokenType::RightBracket) {
+                loop {
+                    let (m, _) = self.tok_match(vec![TokenType::DotDotDot]);
+                    if m {
+                        rest = Some(self.identifier("Expect name after '...'.")?);
+                        break;
+                    }
+                    elements.push(self.declaration_target()?);
+                    let (m, _) = self.tok_match(vec![TokenType::Comma]);
+                    if !m {
+                        break;
+                    }
+                }
+            }
+            self.consume(
+                TokenType::RightBracket,
+                "Expect ']' after list pattern.".to_string(),
+            )?;
+            return Ok(Target::List {
+                bracket,
+                elements,
+                rest,
+            });
+        }
+
+        let (m, t) = self.tok_match(vec![TokenType::LeftBrace]);
+        if m {
+            let brace = match t {
+                Some(t) => t,
+                None => panic!("declaration_target: tok_match returned True and None"),
+            };
+            let mut names = Vec::new();
+            loop {
+                names.push(self.identifier("Expect field name.")?);
+                let (m, _) = self.tok_match(vec![TokenType::Comma]);
+                if !m {
+                    break;
+                }
+            }
+            self.consume(
+                TokenType::RightBrace,
+                "Expect '}' after field names.".to_string(),
+            )?;
+            return Ok(Target::Fields { brace, names });
+        }
+
+        let name = self.identifier("Expect variable name.")?;
+        Ok(Target::Variable { name })
+    }
+
+    fn module_path(&mut self, keyword: &str) -> Result<Token<'src>, ParseError> {
+        let msg = format!("Expect module path string after '{}'.", keyword);
+        match self.consume(TokenType::String, msg)? {
+            Some(t) => Ok(t),
+            None => panic!("module_path: consume returned Ok and None"),
+        }
+    }
+
+    fn identifier(&mut self, msg: &str) -> Result<Token<'src>, ParseError> {
+        match self.consume(TokenType::Identifier, msg.to_string())? {
+            Some(t) => Ok(t),
+            None => panic!("identifier: consume returned Ok and None"),
+        }
+    }
+
+    /// Parses the rest of `class Name with A, B { members }` after the
+    /// keyword; the `with` clause is optional.
+    fn class_declaration(&mut self) -> Result<Statement<'src>, ParseError> {
+        let name = self.identifier("Expect class name.")?;
+        let mut traits = Vec::new();
+        let (m, _) = self.tok_match(vec![TokenType::With]);
+        if m {
+            loop {
+                traits.push(self.identifier("Expect trait name.")?);
+                let (m, _) = self.tok_match(vec![TokenType::Comma]);
+                if !m {
+                    break;
+                }
+            }
+        }
+        let methods = self.members("class")?;
+        Ok(Statement::Class {
+            name,
+            traits,
+            methods,
+        })
+    }
+
+    /// Parses the braced body of a class or trait. A member is a method
+    /// `name(params) { ... }`, a getter `name { ... }` or a setter
+    /// `set name(value) { ... }`, and any of them can be prefixed with
+    /// `class` to put it on the class itself.
+    fn members(&mut self, kind: &str) -> Result<Vec<Method<'src>>, ParseError> {
+        self.consume(
+            TokenType::LeftBrace,
+            format!("Expect '{{' before {} body.", kind),
+        )?;
+        let mut methods = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            methods.push(self.method()?);
+        }
+        self.consume(
+            TokenType::RightBrace,
+            format!("Expect '}}' after {} body.", kind),
+        )?;
+        Ok(methods)
+    }
+
+    fn method(&mut self) -> Result<Method<'src>, ParseError> {
+        let (class_level, _) = self.tok_match(vec![TokenType::Class]);
+        // `set` is only special when another name follows it, so a method
+        // can still be called `set`.
+        let setter = self.current.tok_type == TokenType::Identifier
+            && self.current.lexeme == "set"
+            && self.check_ahead(1, TokenType::Identifier);
+        if setter {
+            self.advance();
+        }
+        let name = self.identifier("Expect member name.")?;
+
+        let (m, _) = self.tok_match(vec![TokenType::LeftParen]);
+        if !m {
+            if setter {
+                self.consume(
+                    TokenType::LeftParen,
+                    "Expect '(' after setter name.".to_string(),
+                )?;
+            }
+            let body = self.function_body()?;
+            return Ok(Method {
+                name,
+                kind: MethodKind::Getter,
+                class_level,
+                lambda: Rc::new(Lambda {
+                    params: Vec::new(),
+                    rest: None,
+                    body,
+                }),
+            });
+        }
+
+        let (params, rest) = self.parameters(Vec::new())?;
+        if setter && (params.len() != 1 || params[0].default.is_some() || rest.is_some()) {
+            return Err(self.error(name, "Setter must take exactly one parameter.".to_string()));
+        }
+        let body = self.function_body()?;
+        Ok(Method {
+            name,
+            kind: if setter {
+                MethodKind::Setter
+            } else {
+                MethodKind::Method
+            },
+            class_level,
+            lambda: Rc::new(Lambda { params, rest, body }),
+        })
+    }
+
+    fn try_statement(&mut self) -> Result<Statement<'src>, ParseError> {
+        self.consume(TokenType::LeftBrace, "Expect '{' after 'try'.".to_string())?;
+        let body = self.block()?;
+
+        let mut catch = None;
+        let (m, _) = self.tok_match(vec![TokenType::Catch]);
+        if m {
+            self.consume(
+                TokenType::LeftParen,
+                "Expect '(' after 'catch'.".to_string(),
+            )?;
+            let name = match self.consume(
+                TokenType::Identifier,
+                "Expect exception variable name.".to_string(),
+            )? {
+                Some(t) => t,
+                None => panic!("try_statement: consume returned Ok and None"),
+            };
+            self.consume(
+                TokenType::RightParen,
+                "Expect ')' after exception variable.".to_string(),
+            )?;
+            self.consume(
+                TokenType::LeftBrace,
+                "Expect '{' before catch body.".to_string(),
+            )?;
+            catch = Some((name, self.block()?));
+        }
+
+        let mut finally = None;
+        let (m, _) = self.tok_match(vec![TokenType::Finally]);
+        if m {
+            self.consume(
+                TokenType::LeftBrace,
+                "Expect '{' after 'finally'.".to_string(),
+            )?;
+            finally = Some(self.block()?);
+        }
+
+        if catch.is_none() && finally.is_none() {
+            let t = self.current.clone();
+            return Err(self.error(
+                t,
+                "Expect 'catch' or 'finally' after try block.".to_string(),
+            ));
+        }
+        Ok(Statement::Try {
+            body,
+            catch,
+            finally,
+        })
+    }
+
+    fn for_in_statement(&mut self) -> Result<Statement<'src>, ParseError> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.".to_string())?;
+        let variable = match self.consume(
+            TokenType::Identifier,
+            "Expect loop variable name.".to_string(),
+        )? {
+            Some(t) => t,
+            None => panic!("for_in_statement: consume returned Ok and None"),
+        };
+        let keyword = match self.consume(
+            TokenType::In,
+            "Expect 'in' after loop variable.".to_string(),
+        )? {
+            Some(t) => t,
+            None => panic!("for_in_statement: consume returned Ok and None"),
+        };
+        let iterable = self.expression()?;
+        self.consume(
+            TokenType::RightParen,
+            "Expect ')' after for-in clause.".to_string(),
+        )?;
+
+        let body = self.loop_body()?;
+        Ok(Statement::ForIn {
+            variable,
+            keyword,
+            iterable,
+            body,
+        })
+    }
+
+    /// Error production for a binary operator found where an operand was
+    /// expected, as in `* 3`. The error is reported and the right-hand
+    /// operand is parsed at the operator's precedence and returned in place
+    /// of the whole expression, so parsing can continue past it.
+    fn missing_left_operand(&mut self) -> Result<Option<Expression<'src>>, ParseError> {
+        let tt = self.current.tok_type;
+        if !matches!(
+            tt,
+            TokenType::BangEqual
+                | TokenType::EqualEqual
+                | TokenType::Greater
+                | TokenType::GreaterEqual
+                | TokenType::Less
+                | TokenType::LessEqual
+                | TokenType::Pipe
+                | TokenType::Caret
+                | TokenType::Ampersand
+                | TokenType::LessLess
+                | TokenType::GreaterGreater
+                | TokenType::Plus
+                | TokenType::Slash
+                | TokenType::Star
+                | TokenType::Percent
+                | TokenType::Div
+                | TokenType::StarStar
+        ) {
+            return Ok(None);
+        }
+
+        let operator = self.current.clone();
+        self.advance();
+        let msg = format!("Missing left-hand operand for '{}'.", operator.lexeme);
+        let err = self.error(operator, msg);
+        eprintln!("{}", err);
+        self.production_error.get_or_insert(err);
+
+        let right = match tt {
+            TokenType::BangEqual | TokenType::EqualEqual => self.comparison()?,
+            TokenType::Greater
+            | TokenType::GreaterEqual
+            | TokenType::Less
+            | TokenType::LessEqual => self.bit_or()?,
+            TokenType::Pipe => self.bit_xor()?,
+            TokenType::Caret => self.bit_and()?,
+            TokenType::Ampersand => self.shift()?,
+            TokenType::LessLess | TokenType::GreaterGreater => self.term()?,
+            TokenType::Plus => self.factor()?,
+            _ => self.unary()?,
+        };
+        Ok(Some(right))
+    }
+
+    fn unary(&mut self) -> Result<Expression<'src>, ParseError> {
+        //println!("parsing unary");
+        let (m, t) = self.tok_match(vec![TokenType::Bang, TokenType::Minus, TokenType::Tilde]);
+        if !m {
+            let p = self.power()?;
+            return Ok(p);
+        }
+        match t {
+            Some(t) => {
+                let operator = t;
+                let right = self.unary()?;
+                return Ok(Expression::Unary {
+                    operator,
+                    right: Box::new(right),
+                });
+            }
+            None => {
+                let t = self.current.clone();
+                let e = self.error(t, "Expected unary.".to_string());
+                Err(e)
+            }
+        }
+    }
+
+    /// Parses `[index]` or `[start:end]` after the opening bracket. Either
+    /// bound of a slice may be left out.
+    fn subscript(
+        &mut self,
+        object: Expression<'src>,
+        bracket: Token<'src>,
+    ) -> Result<Expression<'src>, ParseError> {
+        let mut start = None;
+        if !self.check(TokenType::Colon) {
+            start = Some(Box::new(self.expression()?));
+        }
+        let (m, _) = self.tok_match(vec![TokenType::Colon]);
+        if !m {
+            self.consume(
+                TokenType::RightBracket,
+                "Expect ']' after index.".to_string(),
+            )?;
+            return match start {
+                Some(index) => Ok(Expression::Index {
+                    object: Box::new(object),
+                    bracket,
+                    index,
+                }),
+                None => panic!("subscript: no index and no colon"),
+            };
+        }
+
+        let mut end = None;
+        if !self.check(TokenType::RightBracket) {
+            end = Some(Box::new(self.expression()?));
+        }
+        self.consume(
+            TokenType::RightBracket,
+            "Expect ']' after slice.".to_string(),
+        )?;
+        Ok(Expression::Slice {
+            object: Box::new(object),
+            bracket,
+            start,
+            end,
+        })
+    }
+
+    fn call(&mut self) -> Result<Expression<'src>, ParseError> {
+        let mut expr = self.primary()?;
+        loop {
+            let (m, t) = self.tok_match(vec![
+                TokenType::LeftParen,
+                TokenType::LeftBracket,
+                TokenType::Dot,
+            ]);
+            if !m {
+                break;
+            }
+
+            match t {
+                Some(bracket) if bracket.tok_type == TokenType::LeftBracket => {
+                    expr = self.subscript(expr, bracket)?;
+                }
+                Some(dot) if dot.tok_type == TokenType::Dot => {
+                    let name = self.identifier("Expect property name after '.'.")?;
+                    expr = Expression::Property {
+                        object: Box::new(expr),
+                        name,
+                    };
+                }
+                Some(paren) => {
+                    let mut arguments = Vec::new();
+                    let mut named = Vec::new();
+                    if !self.check(TokenType::RightParen) {
+                        loop {
+                            let argument = self.expression()?;
+                            let (_, colon) = self.tok_match(vec![TokenType::Colon]);
+                            match (argument, colon) {
+                                (Expression::Variable { name }, Some(_)) => {
+                                    named.push((name, self.expression()?));
+                                }
+                                (_, Some(colon)) => {
+                                    return Err(self.error(
+                                        colon,
+                                        "Expect parameter name before ':'.".to_string(),
+                                    ));
+                                }
+                                (argument, None) => {
+                                    if let Some((name, _)) = named.last() {
+                                        let name = name.clone();
+                                        return Err(self.error(
+                                            name,
+                                            "Positional argument can't follow a named argument."
+                                                .to_string(),
+                                        ));
+                                    }
+                                    arguments.push(argument);
+                                }
+                            }
+                            let (m, _) = self.tok_match(vec![TokenType::Comma]);
+                            if !m {
+                                break;
+                            }
+                        }
+                    }
+                    self.consume(
+                        TokenType::RightParen,
+                        "Expect ')' after arguments.".to_string(),
+                    )?;
+                    expr = Expression::Call {
+                        callee: Box::new(expr),
+                        paren,
+                        arguments,
+                        named,
+                    };
+                }
+                None => {
+                    break;
+                }
+            }
+        }
+        Ok(expr)
+    }
+
+    fn power(&mut self) -> Result<Expression<'src>, ParseError> {
+        let expr = self.call()?;
+        let (m, t) = self.tok_match(vec![TokenType::StarStar]);
+        if !m {
+            return Ok(expr);
+        }
+        match t {
+            Some(t) => {
+                // Recursing through unary makes ** right-associative and
+                // lets the exponent carry its own sign, as in 2 ** -1.
+                let operator = t;
+                let right = self.unary()?;
+                Ok(Expression::Binary {
+                    left: Box::new(expr),
+                    operator,
+                    right: Box::new(right),
+                })
+            }
+            None => {
+                panic!("power: tok_match returned True and None")
+            }
+        }
+    }
+
+    fn factor(&mut self) -> Result<Expression<'src>, ParseError> {
+        let mut expr = self.unary()?;
+        loop {
+            let (m, t) = self.tok_match(vec![
+                TokenType::Slash,
+                TokenType::Star,
+                TokenType::Percent,
+                TokenType::Div,
+            ]);
+            if !m {
+                break;
+            }
 
             match t {
                 Some(t) => {
@@ -355,7 +2066,7 @@ impl Parser {
         Ok(expr)
     }
 
-    fn term(&mut self) -> Result<Expression, ParseError> {
+    fn term(&mut self) -> Result<Expression<'src>, ParseError> {
         //println!("parsing term");
         let mut expr = self.factor()?;
         loop {
@@ -382,9 +2093,113 @@ impl Parser {
         Ok(expr)
     }
 
-    fn comparison(&mut self) -> Result<Expression, ParseError> {
-        //println!("parsing comparison");
+    fn bit_or(&mut self) -> Result<Expression<'src>, ParseError> {
+        let mut expr = self.bit_xor()?;
+        loop {
+            let (m, t) = self.tok_match(vec![TokenType::Pipe]);
+            if !m {
+                break;
+            }
+
+            match t {
+                Some(t) => {
+                    let operator = t;
+                    let right = self.bit_xor()?;
+                    expr = Expression::Binary {
+                        left: Box::new(expr),
+                        operator,
+                        right: Box::new(right),
+                    };
+                }
+                None => {
+                    break;
+                }
+            }
+        }
+        Ok(expr)
+    }
+
+    fn bit_xor(&mut self) -> Result<Expression<'src>, ParseError> {
+        let mut expr = self.bit_and()?;
+        loop {
+            let (m, t) = self.tok_match(vec![TokenType::Caret]);
+            if !m {
+                break;
+            }
+
+            match t {
+                Some(t) => {
+                    let operator = t;
+                    let right = self.bit_and()?;
+                    expr = Expression::Binary {
+                        left: Box::new(expr),
+                        operator,
+                        right: Box::new(right),
+                    };
+                }
+                None => {
+                    break;
+                }
+            }
+        }
+        Ok(expr)
+    }
+
+    fn bit_and(&mut self) -> Result<Expression<'src>, ParseError> {
+        let mut expr = self.shift()?;
+        loop {
+            let (m, t) = self.tok_match(vec![TokenType::Ampersand]);
+            if !m {
+                break;
+            }
+
+            match t {
+                Some(t) => {
+                    let operator = t;
+                    let right = self.shift()?;
+                    expr = Expression::Binary {
+                        left: Box::new(expr),
+                        operator,
+                        right: Box::new(right),
+                    };
+                }
+                None => {
+                    break;
+                }
+            }
+        }
+        Ok(expr)
+    }
+
+    fn shift(&mut self) -> Result<Expression<'src>, ParseError> {
         let mut expr = self.term()?;
+        loop {
+            let (m, t) = self.tok_match(vec![TokenType::LessLess, TokenType::GreaterGreater]);
+            if !m {
+                break;
+            }
+
+            match t {
+                Some(t) => {
+                    let operator = t;
+                    let right = self.term()?;
+                    expr = Expression::Binary {
+                        left: Box::new(expr),
+                        operator,
+                        right: Box::new(right),
+                    };
+                }
+                None => {
+                    break;
+                }
+            }
+        }
+        Ok(expr)
+    }
+
+    fn comparison(&mut self) -> Result<Expression<'src>, ParseError> {
+        //println!("parsing comparison");
+        let mut expr = self.bit_or()?;
         loop {
             let (m, t) = self.tok_match(vec![
                 TokenType::Greater,
@@ -399,7 +2214,7 @@ impl Parser {
             match t {
                 Some(t) => {
                     let operator = t;
-                    let right = self.term()?;
+                    let right = self.bit_or()?;
                     expr = Expression::Binary {
                         left: Box::new(expr),
                         operator,
@@ -0,0 +1,47 @@
+/// The integers from `start` up to but not including `end`, `step` apart,
+/// counting down when `step` is negative. A range is computed as it is
+/// iterated, so its size doesn't matter. `step` is never zero.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Range {
+    pub start: i64,
+    pub end: i64,
+    pub step: i64,
+}
+
+impl Range {
+    /// The number of integers in the range. It can exceed `i64::MAX`.
+    pub fn len(&self) -> u64 {
+        let (start, end, step) = (self.start as i128, self.end as i128, self.step as i128);
+        let n = if step > 0 && start < end {
+            (end - start - 1) / step + 1
+        } else if step < 0 && start > end {
+            (start - end - 1) / -step + 1
+        } else {
+            0
+        };
+        n as u64
+    }
+
+    /// The `i`th integer of the range, if it has that many.
+    pub fn get(&self, i: u64) -> Option<i64> {
+        if i >= self.len() {
+            return None;
+        }
+        Some((self.start as i128 + i as i128 * self.step as i128) as i64)
+    }
+}
+
+impl Iterator for Range {
+    type Item = i64;
+
+    fn next(&mut self) -> Option<i64> {
+        let i = self.start;
+        if (self.step > 0 && i >= self.end) || (self.step < 0 && i <= self.end) {
+            return None;
+        }
+        // Past the limits of i64 the range is over, since `end` is within
+        // them.
+        self.start = i.checked_add(self.step).unwrap_or(self.end);
+        Some(i)
+    }
+}
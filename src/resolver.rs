@@ -0,0 +1,451 @@
+use std::collections::HashMap;
+use std::{error::Error, fmt};
+
+use crate::interpreter::trait_conflict;
+use crate::parser::{Expression, Lambda, MatchArm, Method, Pattern, Statement, Target};
+use crate::token::Token;
+
+/// An error found by the resolver, reported like a parse error.
+#[derive(Debug)]
+pub struct ResolveError {
+    line: i64,
+    msg: String,
+}
+
+impl Error for ResolveError {}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.line, self.msg)
+    }
+}
+
+/// Walks a parsed program before it runs, tracking which names each scope
+/// declares, and rejects assignments to constants. Scopes mirror the
+/// environments the interpreter creates, so a name resolves here to the
+/// same declaration it will find at runtime.
+pub struct Resolver<'src> {
+    // Innermost scope last. A name maps to the token that declared it as a
+    // constant, or `None` for any other kind of declaration.
+    scopes: Vec<HashMap<&'src str, Option<Token<'src>>>>,
+    // For each scope, the traits it declares and the members they provide,
+    // as (class_level, name) pairs.
+    traits: Vec<HashMap<&'src str, Vec<(bool, &'src str)>>>,
+    // Whether the code being resolved is inside a class body, where `this`
+    // can be used.
+    in_class: bool,
+    errors: Vec<ResolveError>,
+}
+
+impl<'src> Resolver<'src> {
+    pub fn new() -> Resolver<'src> {
+        Resolver {
+            scopes: vec![HashMap::new()],
+            traits: vec![HashMap::new()],
+            in_class: false,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Resolves a program or module, whose statements share one top-level
+    /// scope. Every error found is printed, and the first is returned.
+    pub fn resolve(mut self, statements: &[Statement<'src>]) -> Result<(), ResolveError> {
+        self.statements(statements);
+        for e in &self.errors {
+            eprintln!("{}", e);
+        }
+        match self.errors.into_iter().next() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    fn error(&mut self, t: &Token<'src>, m: String) {
+        self.errors.push(ResolveError {
+            line: t.line,
+            msg: format!("at '{}' {}", t.lexeme, m),
+        });
+    }
+
+    fn declare(&mut self, name: &Token<'src>, constant: bool) {
+        let scope = self.scopes.last_mut().expect("declare: no scope");
+        if let Some(Some(previous)) = scope.get(name.lexeme) {
+            let msg = format!(
+                "Constant '{}' is already declared on line {}.",
+                name.lexeme, previous.line
+            );
+            self.error(name, msg);
+            return;
+        }
+        let declaration = if constant { Some(name.clone()) } else { None };
+        scope.insert(name.lexeme, declaration);
+        let traits = self.traits.last_mut().expect("declare: no scope");
+        traits.remove(name.lexeme);
+    }
+
+    fn assign(&mut self, name: &Token<'src>) {
+        let declaration = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name.lexeme));
+        if let Some(Some(constant)) = declaration {
+            let msg = format!(
+                "Can't assign to constant '{}' declared on line {}.",
+                name.lexeme, constant.line
+            );
+            self.error(name, msg);
+        }
+    }
+
+    fn declare_target(&mut self, target: &Target<'src>) {
+        match target {
+            Target::Variable { name } => self.declare(name, false),
+            Target::Index { object, index, .. } => {
+                self.expression(object);
+                self.expression(index);
+            }
+            Target::Property { object, .. } => self.expression(object),
+            Target::List { elements, rest, .. } => {
+                for e in elements {
+                    self.declare_target(e);
+                }
+                if let Some(rest) = rest {
+                    self.declare(rest, false);
+                }
+            }
+            Target::Fields { names, .. } => {
+                for name in names {
+                    self.declare(name, false);
+                }
+            }
+        }
+    }
+
+    fn assign_target(&mut self, target: &Target<'src>) {
+        match target {
+            Target::Variable { name } => self.assign(name),
+            Target::Index { object, index, .. } => {
+                self.expression(object);
+                self.expression(index);
+            }
+            Target::Property { object, .. } => self.expression(object),
+            Target::List { elements, rest, .. } => {
+                for e in elements {
+                    self.assign_target(e);
+                }
+                if let Some(rest) = rest {
+                    self.assign(rest);
+                }
+            }
+            Target::Fields { names, .. } => {
+                for name in names {
+                    self.assign(name);
+                }
+            }
+        }
+    }
+
+    /// Runs `f` in a new innermost scope.
+    fn scoped(&mut self, f: impl FnOnce(&mut Resolver<'src>)) {
+        self.scopes.push(HashMap::new());
+        self.traits.push(HashMap::new());
+        f(self);
+        self.traits.pop();
+        self.scopes.pop();
+    }
+
+    /// The members of the trait `name` refers to, if it refers to one
+    /// declared in this program rather than, say, an imported one.
+    fn trait_members(&self, name: &Token<'src>) -> Option<Vec<(bool, &'src str)>> {
+        let depth = self
+            .scopes
+            .iter()
+            .rposition(|scope| scope.contains_key(name.lexeme))?;
+        self.traits[depth].get(name.lexeme).cloned()
+    }
+
+    /// Reports members that two of a class's traits provide and the class
+    /// doesn't declare itself.
+    fn check_traits(
+        &mut self,
+        name: &Token<'src>,
+        traits: &[Token<'src>],
+        methods: &[Method<'src>],
+    ) {
+        let mut provided: Vec<(bool, &'src str, &'src str)> = Vec::new();
+        for class_trait in traits {
+            let members = match self.trait_members(class_trait) {
+                Some(members) => members,
+                None => continue,
+            };
+            for (class_level, member) in members {
+                let declared = methods
+                    .iter()
+                    .any(|m| m.class_level == class_level && m.name.lexeme == member);
+                if declared {
+                    continue;
+                }
+                let earlier = provided.iter().find(|(level, m, t)| {
+                    *level == class_level && *m == member && *t != class_trait.lexeme
+                });
+                match earlier {
+                    Some((_, _, other)) => {
+                        let msg = trait_conflict(name.lexeme, member, other, class_trait.lexeme);
+                        self.error(name, msg);
+                    }
+                    None => provided.push((class_level, member, class_trait.lexeme)),
+                }
+            }
+        }
+    }
+
+    /// Resolves the members of a class or trait body, where `this` can be
+    /// used.
+    fn methods(&mut self, methods: &[Method<'src>]) {
+        let in_class = std::mem::replace(&mut self.in_class, true);
+        for method in methods {
+            // Each method runs in a scope that binds `this`.
+            self.scoped(|r| r.lambda(&method.lambda));
+        }
+        self.in_class = in_class;
+    }
+
+    fn expression(&mut self, expr: &Expression<'src>) {
+        match expr {
+            Expression::Literal { .. } | Expression::Variable { .. } => {}
+            Expression::Binary { left, right, .. } => {
+                self.expression(left);
+                self.expression(right);
+            }
+            Expression::Unary { right, .. } => self.expression(right),
+            Expression::Grouping { group } => self.expression(group),
+            Expression::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(condition);
+                self.expression(then_branch);
+                self.expression(else_branch);
+            }
+            Expression::Assign { name, value, .. } => {
+                self.expression(value);
+                self.assign(name);
+            }
+            Expression::Call {
+                callee,
+                arguments,
+                named,
+                ..
+            } => {
+                self.expression(callee);
+                for a in arguments {
+                    self.expression(a);
+                }
+                for (_, value) in named {
+                    self.expression(value);
+                }
+            }
+            Expression::Lambda { lambda } => self.lambda(lambda),
+            Expression::Match { subject, arms, .. } => {
+                self.expression(subject);
+                for arm in arms {
+                    self.arm(arm);
+                }
+            }
+            Expression::Destructure { target, value, .. } => {
+                self.expression(value);
+                self.assign_target(target);
+            }
+            Expression::List { elements, .. } => {
+                for e in elements {
+                    self.expression(e);
+                }
+            }
+            Expression::Map { entries, .. } => {
+                for (k, v) in entries {
+                    self.expression(k);
+                    self.expression(v);
+                }
+            }
+            Expression::Index { object, index, .. } => {
+                self.expression(object);
+                self.expression(index);
+            }
+            Expression::SetIndex {
+                object,
+                index,
+                value,
+                ..
+            } => {
+                self.expression(object);
+                self.expression(index);
+                self.expression(value);
+            }
+            Expression::Slice {
+                object, start, end, ..
+            } => {
+                self.expression(object);
+                for bound in [start, end].into_iter().flatten() {
+                    self.expression(bound);
+                }
+            }
+            Expression::Property { object, .. } => self.expression(object),
+            Expression::SetProperty { object, value, .. } => {
+                self.expression(object);
+                self.expression(value);
+            }
+            Expression::This { keyword } => {
+                if !self.in_class {
+                    self.error(keyword, "Can't use 'this' outside of a class.".to_string());
+                }
+            }
+        }
+    }
+
+    fn lambda(&mut self, lambda: &Lambda<'src>) {
+        self.scoped(|r| {
+            for param in &lambda.params {
+                if let Some(default) = &param.default {
+                    r.expression(default);
+                }
+                r.declare(&param.name, false);
+            }
+            if let Some(rest) = &lambda.rest {
+                r.declare(rest, false);
+            }
+            r.statements(&lambda.body);
+        });
+    }
+
+    fn arm(&mut self, arm: &MatchArm<'src>) {
+        self.scoped(|r| {
+            r.pattern(&arm.pattern);
+            if let Some(guard) = &arm.guard {
+                r.expression(guard);
+            }
+            r.expression(&arm.body);
+        });
+    }
+
+    fn pattern(&mut self, pattern: &Pattern<'src>) {
+        match pattern {
+            Pattern::Wildcard | Pattern::Literal { .. } => {}
+            Pattern::Binding { name } => self.declare(name, false),
+            Pattern::List { elements: patterns }
+            | Pattern::Alternatives {
+                alternatives: patterns,
+            } => {
+                for p in patterns {
+                    self.pattern(p);
+                }
+            }
+        }
+    }
+
+    fn statements(&mut self, statements: &[Statement<'src>]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement<'src>) {
+        match statement {
+            Statement::Expression { expr } => self.expression(expr),
+            Statement::Return { value } => {
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+            }
+            Statement::Var { target, value } => {
+                if let Some((_, value)) = value {
+                    self.expression(value);
+                }
+                self.declare_target(target);
+            }
+            Statement::Const { name, value } => {
+                self.expression(value);
+                self.declare(name, true);
+            }
+            Statement::Block { statements } => self.scoped(|r| r.statements(statements)),
+            Statement::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(condition);
+                self.statement(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch);
+                }
+            }
+            Statement::While {
+                condition,
+                body,
+                increment,
+            } => {
+                self.expression(condition);
+                self.statement(body);
+                if let Some(increment) = increment {
+                    self.expression(increment);
+                }
+            }
+            Statement::ForIn {
+                variable,
+                iterable,
+                body,
+                ..
+            } => {
+                self.expression(iterable);
+                self.scoped(|r| {
+                    r.declare(variable, false);
+                    r.statement(body);
+                });
+            }
+            Statement::Break | Statement::Continue => {}
+            Statement::Throw { value, .. } => self.expression(value),
+            Statement::Import { name, .. } => self.declare(name, false),
+            Statement::FromImport { names, .. } => {
+                for name in names {
+                    self.declare(name, false);
+                }
+            }
+            Statement::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                self.scoped(|r| r.statements(body));
+                if let Some((name, handler)) = catch {
+                    self.scoped(|r| {
+                        r.declare(name, false);
+                        r.statements(handler);
+                    });
+                }
+                if let Some(finally) = finally {
+                    self.scoped(|r| r.statements(finally));
+                }
+            }
+            Statement::Class {
+                name,
+                traits,
+                methods,
+            } => {
+                self.check_traits(name, traits, methods);
+                self.declare(name, false);
+                self.methods(methods);
+            }
+            Statement::Trait { name, methods } => {
+                self.declare(name, false);
+                let members = methods
+                    .iter()
+                    .map(|m| (m.class_level, m.name.lexeme))
+                    .collect();
+                let traits = self.traits.last_mut().expect("statement: no scope");
+                traits.insert(name.lexeme, members);
+                self.methods(methods);
+            }
+        }
+    }
+}
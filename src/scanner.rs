@@ -1,64 +1,104 @@
-use crate::error;
+use crate::bigint::BigInt;
 use crate::is_alpha;
 use crate::is_alphanumeric;
-use crate::token::{Token, TokenType};
+use crate::token::{Literal, Token, TokenType};
 use std::collections::HashMap;
+use std::{error::Error, fmt};
 
-pub struct Scanner {
-    source: String,
-    tokens: Vec<Token>,
-    chars: Vec<char>,
-    text_buffer: String,
+#[derive(Clone)]
+pub struct Scanner<'src> {
+    source: &'src str,
     start: usize,
     current: usize,
     line: i64,
-    reserved: HashMap<String, TokenType>,
+    reserved: HashMap<&'static str, TokenType>,
+    emitted_eof: bool,
 }
 
-pub fn new_scanner(source: String) -> Scanner {
+#[derive(Debug)]
+pub struct ScanError {
+    pub line: i64,
+    pub msg: String,
+}
+
+impl Error for ScanError {}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.line, self.msg)
+    }
+}
+
+pub fn new_scanner(source: &str) -> Scanner<'_> {
     let mut s = Scanner {
         source,
-        tokens: Vec::new(),
-        chars: Vec::new(),
-        text_buffer: "".to_string(),
         start: 0,
         current: 0,
         line: 1,
         reserved: HashMap::new(),
+        emitted_eof: false,
     };
-    s.chars = s.source.chars().collect();
-    s.reserved.insert(String::from("and"), TokenType::And);
-    s.reserved.insert(String::from("class"), TokenType::Class);
-    s.reserved.insert(String::from("else"), TokenType::Else);
-    s.reserved.insert(String::from("false"), TokenType::False);
-    s.reserved.insert(String::from("for"), TokenType::For);
-    s.reserved.insert(String::from("fun"), TokenType::Fun);
-    s.reserved.insert(String::from("if"), TokenType::If);
-    s.reserved.insert(String::from("nil"), TokenType::Nil);
-    s.reserved.insert(String::from("or"), TokenType::Or);
-    s.reserved.insert(String::from("print"), TokenType::Print);
-    s.reserved.insert(String::from("return"), TokenType::Return);
-    s.reserved.insert(String::from("super"), TokenType::Super);
-    s.reserved.insert(String::from("this"), TokenType::This);
-    s.reserved.insert(String::from("true"), TokenType::True);
-    s.reserved.insert(String::from("var"), TokenType::Var);
-    s.reserved.insert(String::from("while"), TokenType::While);
+    s.reserved.insert("and", TokenType::And);
+    s.reserved.insert("as", TokenType::As);
+    s.reserved.insert("break", TokenType::Break);
+    s.reserved.insert("catch", TokenType::Catch);
+    s.reserved.insert("class", TokenType::Class);
+    s.reserved.insert("const", TokenType::Const);
+    s.reserved.insert("continue", TokenType::Continue);
+    s.reserved.insert("div", TokenType::Div);
+    s.reserved.insert("else", TokenType::Else);
+    s.reserved.insert("false", TokenType::False);
+    s.reserved.insert("finally", TokenType::Finally);
+    s.reserved.insert("for", TokenType::For);
+    s.reserved.insert("from", TokenType::From);
+    s.reserved.insert("fun", TokenType::Fun);
+    s.reserved.insert("if", TokenType::If);
+    s.reserved.insert("import", TokenType::Import);
+    s.reserved.insert("in", TokenType::In);
+    s.reserved.insert("match", TokenType::Match);
+    s.reserved.insert("nil", TokenType::Nil);
+    s.reserved.insert("or", TokenType::Or);
+    s.reserved.insert("print", TokenType::Print);
+    s.reserved.insert("return", TokenType::Return);
+    s.reserved.insert("super", TokenType::Super);
+    s.reserved.insert("this", TokenType::This);
+    s.reserved.insert("throw", TokenType::Throw);
+    s.reserved.insert("trait", TokenType::Trait);
+    s.reserved.insert("true", TokenType::True);
+    s.reserved.insert("try", TokenType::Try);
+    s.reserved.insert("var", TokenType::Var);
+    s.reserved.insert("while", TokenType::While);
+    s.reserved.insert("with", TokenType::With);
+    s.skip_preamble();
     s
 }
 
-impl Scanner {
-    fn parse_identifier(&mut self) -> Option<Token> {
+impl<'src> Scanner<'src> {
+    /// Skips a leading UTF-8 byte-order mark and a `#!` interpreter line so
+    /// scripts can be made executable. The newline ending the `#!` line is
+    /// left in place so line numbers still count from the top of the file.
+    fn skip_preamble(&mut self) {
+        if self.source.starts_with('\u{feff}') {
+            self.current += '\u{feff}'.len_utf8();
+        }
+        if self.source[self.current..].starts_with("#!") {
+            while self.peek() != '\n' && !self.is_at_end() {
+                self.advance();
+            }
+        }
+    }
+
+    fn parse_identifier(&mut self) -> Option<Token<'src>> {
         while is_alphanumeric(self.peek()) {
             self.advance();
         }
-        let text = &self.text_buffer;
-        match self.reserved.get(text).copied() {
+        match self.reserved.get(self.lexeme()).copied() {
             Some(t) => self.add_token(t),
             None => self.add_token(TokenType::Identifier),
         }
     }
 
-    fn parse_number(&mut self) -> Option<Token> {
+    fn parse_number(&mut self) -> Result<Option<Token<'src>>, ScanError> {
         while self.peek().is_ascii_digit() {
             self.advance();
         }
@@ -69,13 +109,39 @@ impl Scanner {
             while self.peek().is_ascii_digit() {
                 self.advance();
             }
+
+            let num = self.lexeme().parse::<f64>().unwrap();
+            return Ok(Some(self.add_token_literal(
+                TokenType::Number,
+                Literal::Float { lit: num },
+            )));
+        }
+
+        // A trailing `n` makes a big integer, unless it starts a name, as
+        // in `123name`.
+        if self.peek() == 'n' && !is_alphanumeric(self.peek_next()) {
+            let digits = self.lexeme();
+            self.advance();
+            let num = BigInt::parse(digits).unwrap();
+            return Ok(Some(self.add_token_literal(
+                TokenType::Number,
+                Literal::BigInt { lit: num },
+            )));
         }
 
-        let num = self.text_buffer.parse::<f64>().unwrap();
-        Some(self.add_token_float_literal(TokenType::Number, num))
+        match self.lexeme().parse::<i64>() {
+            Ok(num) => Ok(Some(
+                self.add_token_literal(TokenType::Number, Literal::Integer { lit: num }),
+            )),
+            Err(_) => Err(self.error(format!(
+                "Integer literal {} does not fit in 64 bits.",
+                self.lexeme()
+            ))),
+        }
     }
 
-    fn parse_string(&mut self) -> Option<Token> {
+    fn parse_string(&mut self) -> Result<Option<Token<'src>>, ScanError> {
+        let start_line = self.line;
         while self.peek() != '"' && !self.is_at_end() {
             if self.peek() == '\n' {
                 self.line += 1
@@ -84,175 +150,328 @@ impl Scanner {
         }
 
         if self.is_at_end() {
-            error(self.line, "Unterminated string.".to_string());
+            return Err(ScanError {
+                line: start_line,
+                msg: "Unterminated string.".to_string(),
+            });
         }
 
         // For the closing "
         let _ = self.advance();
 
-        let substring: &[char] = &self.chars[self.start + 1..self.current - 1];
-        let mut s: String = String::new();
-        for c in substring {
-            s += &c.to_string();
+        let s = self.source[self.start + 1..self.current - 1].to_string();
+        Ok(Some(self.add_token_literal(
+            TokenType::String,
+            Literal::String { lit: s },
+        )))
+    }
+
+    fn is_raw_string_start(&self) -> bool {
+        self.source[self.current..]
+            .trim_start_matches('#')
+            .starts_with('"')
+    }
+
+    /// Scans `r"..."` or `r#"..."#`. Any number of `#` may fence the string,
+    /// and it only ends at a quote followed by the same number of `#`.
+    fn parse_raw_string(&mut self) -> Result<Option<Token<'src>>, ScanError> {
+        let start_line = self.line;
+        let mut hashes = 0;
+        while self.peek() == '#' {
+            self.advance();
+            hashes += 1;
         }
+        // For the opening "
+        let _ = self.advance();
 
-        Some(self.add_token_string_literal(TokenType::String, s))
+        let content_start = self.current;
+        let fence = format!("\"{}", "#".repeat(hashes));
+        while !self.source[self.current..].starts_with(&fence) {
+            if self.is_at_end() {
+                return Err(ScanError {
+                    line: start_line,
+                    msg: format!("Unterminated raw string, expected closing {}.", fence),
+                });
+            }
+            if self.advance() == '\n' {
+                self.line += 1;
+            }
+        }
+
+        let s = self.source[content_start..self.current].to_string();
+        self.current += fence.len();
+        Ok(Some(self.add_token_literal(
+            TokenType::String,
+            Literal::String { lit: s },
+        )))
+    }
+
+    /// Scans a `"""` string. The text may span lines and has its common
+    /// leading indentation removed, see `dedent`.
+    fn parse_multiline_string(&mut self) -> Result<Option<Token<'src>>, ScanError> {
+        let start_line = self.line;
+        // For the two remaining opening "
+        self.current += 2;
+
+        let content_start = self.current;
+        while !self.source[self.current..].starts_with("\"\"\"") {
+            if self.is_at_end() {
+                return Err(ScanError {
+                    line: start_line,
+                    msg: "Unterminated multi-line string, expected closing \"\"\".".to_string(),
+                });
+            }
+            if self.advance() == '\n' {
+                self.line += 1;
+            }
+        }
+
+        let s = dedent(&self.source[content_start..self.current]);
+        self.current += 3;
+        Ok(Some(self.add_token_literal(
+            TokenType::String,
+            Literal::String { lit: s },
+        )))
     }
 
     fn is_at_end(&self) -> bool {
         self.current >= self.source.len()
     }
 
-    fn add_token(&self, t: TokenType) -> Option<Token> {
+    fn lexeme(&self) -> &'src str {
+        &self.source[self.start..self.current]
+    }
+
+    fn error(&self, msg: String) -> ScanError {
+        ScanError {
+            line: self.line,
+            msg,
+        }
+    }
+
+    fn add_token(&self, t: TokenType) -> Option<Token<'src>> {
         match t {
-            TokenType::False => Some(self.add_token_bool_literal(t, false)),
-            TokenType::True => Some(self.add_token_bool_literal(t, true)),
+            TokenType::False => Some(self.add_token_literal(t, Literal::Bool { lit: false })),
+            TokenType::True => Some(self.add_token_literal(t, Literal::Bool { lit: true })),
+            TokenType::Nil => Some(self.add_token_literal(t, Literal::Nil)),
             _ => Some(self.add_token_base(t)),
         }
     }
 
-    fn add_token_base(&self, t: TokenType) -> Token {
+    fn add_token_base(&self, t: TokenType) -> Token<'src> {
         Token {
             tok_type: t,
-            lexeme: self.text_buffer.clone(),
+            lexeme: self.lexeme(),
             line: self.line,
-            bool_literal: false,
-            float_literal: 0.0,
-            string_literal: String::new(),
+            literal: None,
         }
     }
 
-    fn add_token_string_literal(&self, t: TokenType, literal: String) -> Token {
+    fn add_token_literal(&self, t: TokenType, literal: Literal) -> Token<'src> {
         let mut t = self.add_token_base(t);
-        t.string_literal = literal;
-        t
-    }
-
-    fn add_token_float_literal(&self, t: TokenType, literal: f64) -> Token {
-        let mut t = self.add_token_base(t);
-        t.float_literal = literal;
-        t
-    }
-
-    fn add_token_bool_literal(&self, t: TokenType, literal: bool) -> Token {
-        let mut t = self.add_token_base(t);
-        t.bool_literal = literal;
+        t.literal = Some(literal);
         t
     }
 
     fn advance(&mut self) -> char {
-        let c = self.chars[self.current];
-        self.text_buffer += &c.to_string();
-        self.current += 1;
+        let c = self.peek();
+        self.current += c.len_utf8();
         c
     }
 
-    fn peek_next(&mut self) -> char {
-        if self.current + 1 >= self.source.len() {
-            return '\0';
-        }
-        return self.chars[self.current + 1];
+    fn peek_next(&self) -> char {
+        let mut chars = self.source[self.current..].chars();
+        chars.next();
+        chars.next().unwrap_or('\0')
     }
 
-    fn peek(&mut self) -> char {
-        if self.is_at_end() {
-            return '\0';
-        }
-        return self.chars[self.current];
+    fn peek(&self) -> char {
+        self.source[self.current..].chars().next().unwrap_or('\0')
     }
 
     fn tok_match(&mut self, expected: char) -> bool {
         if self.is_at_end() {
             return false;
         }
-        if self.chars[self.current] != expected {
+        if self.peek() != expected {
             return false;
         }
-        self.text_buffer += &expected.to_string();
-        self.current += 1;
+        self.current += expected.len_utf8();
         return true;
     }
 
-    pub fn scan_tokens(&mut self) -> Vec<Token> {
-        let mut new_tokens: Vec<Token> = Vec::new();
-        while !self.is_at_end() {
-            self.start = self.current;
-            let c = self.advance();
-            let t = match c {
-                '(' => self.add_token(TokenType::LeftParen),
-                ')' => self.add_token(TokenType::RightParen),
-                '{' => self.add_token(TokenType::LeftBrace),
-                '}' => self.add_token(TokenType::RightBrace),
-                ',' => self.add_token(TokenType::Comma),
-                '.' => self.add_token(TokenType::Dot),
-                '-' => self.add_token(TokenType::Minus),
-                '+' => self.add_token(TokenType::Plus),
-                ';' => self.add_token(TokenType::Semicolon),
-                '*' => self.add_token(TokenType::Star),
-                ' ' => None,
-                '\r' => None,
-                '\t' => None,
-                '\n' => {
-                    self.line += 1;
-                    None
+    /// Scans a single lexeme starting at `self.start`. Whitespace and
+    /// comments produce `Ok(None)`.
+    fn scan_token(&mut self) -> Result<Option<Token<'src>>, ScanError> {
+        let c = self.advance();
+        let t = match c {
+            '(' => self.add_token(TokenType::LeftParen),
+            ')' => self.add_token(TokenType::RightParen),
+            '{' => self.add_token(TokenType::LeftBrace),
+            '}' => self.add_token(TokenType::RightBrace),
+            '[' => self.add_token(TokenType::LeftBracket),
+            ']' => self.add_token(TokenType::RightBracket),
+            ',' => self.add_token(TokenType::Comma),
+            '.' => {
+                if self.peek() == '.' && self.peek_next() == '.' {
+                    self.advance();
+                    self.advance();
+                    self.add_token(TokenType::DotDotDot)
+                } else {
+                    self.add_token(TokenType::Dot)
                 }
-                '!' => {
-                    if self.tok_match('=') {
-                        self.add_token(TokenType::BangEqual)
-                    } else {
-                        self.add_token(TokenType::Bang)
-                    }
+            }
+            ';' => self.add_token(TokenType::Semicolon),
+            '&' => self.add_token(TokenType::Ampersand),
+            '|' => self.add_token(TokenType::Pipe),
+            '^' => self.add_token(TokenType::Caret),
+            '~' => self.add_token(TokenType::Tilde),
+            '?' => self.add_token(TokenType::Question),
+            ':' => self.add_token(TokenType::Colon),
+            '-' => {
+                if self.tok_match('=') {
+                    self.add_token(TokenType::MinusEqual)
+                } else {
+                    self.add_token(TokenType::Minus)
                 }
-                '=' => {
-                    if self.tok_match('=') {
-                        self.add_token(TokenType::EqualEqual)
-                    } else {
-                        self.add_token(TokenType::Equal)
-                    }
+            }
+            '+' => {
+                if self.tok_match('=') {
+                    self.add_token(TokenType::PlusEqual)
+                } else {
+                    self.add_token(TokenType::Plus)
                 }
-                '>' => {
-                    if self.tok_match('=') {
-                        self.add_token(TokenType::GreaterEqual)
-                    } else {
-                        self.add_token(TokenType::Greater)
-                    }
+            }
+            '*' => {
+                if self.tok_match('*') {
+                    self.add_token(TokenType::StarStar)
+                } else if self.tok_match('=') {
+                    self.add_token(TokenType::StarEqual)
+                } else {
+                    self.add_token(TokenType::Star)
                 }
-                '<' => {
-                    if self.tok_match('=') {
-                        self.add_token(TokenType::LessEqual)
-                    } else {
-                        self.add_token(TokenType::Less)
-                    }
+            }
+            '%' => {
+                if self.tok_match('=') {
+                    self.add_token(TokenType::PercentEqual)
+                } else {
+                    self.add_token(TokenType::Percent)
                 }
-                '/' => {
-                    if self.tok_match('/') {
-                        while self.peek() != '\n' && !self.is_at_end() {
-                            self.advance();
-                        }
-                        None
-                    } else {
-                        self.add_token(TokenType::Slash)
-                    }
+            }
+            ' ' => None,
+            '\r' => None,
+            '\t' => None,
+            '\n' => {
+                self.line += 1;
+                None
+            }
+            '!' => {
+                if self.tok_match('=') {
+                    self.add_token(TokenType::BangEqual)
+                } else {
+                    self.add_token(TokenType::Bang)
+                }
+            }
+            '=' => {
+                if self.tok_match('=') {
+                    self.add_token(TokenType::EqualEqual)
+                } else if self.tok_match('>') {
+                    self.add_token(TokenType::Arrow)
+                } else {
+                    self.add_token(TokenType::Equal)
                 }
-                '"' => self.parse_string(),
-                _ => {
-                    if c.is_ascii_digit() {
-                        self.parse_number()
-                    } else if is_alpha(c) {
-                        self.parse_identifier()
-                    } else {
-                        let msg = format!("Unexpected character: {}", c);
-                        error(self.line, msg);
-                        None
+            }
+            '>' => {
+                if self.tok_match('=') {
+                    self.add_token(TokenType::GreaterEqual)
+                } else if self.tok_match('>') {
+                    self.add_token(TokenType::GreaterGreater)
+                } else {
+                    self.add_token(TokenType::Greater)
+                }
+            }
+            '<' => {
+                if self.tok_match('=') {
+                    self.add_token(TokenType::LessEqual)
+                } else if self.tok_match('<') {
+                    self.add_token(TokenType::LessLess)
+                } else {
+                    self.add_token(TokenType::Less)
+                }
+            }
+            '/' => {
+                if self.tok_match('/') {
+                    while self.peek() != '\n' && !self.is_at_end() {
+                        self.advance();
                     }
+                    None
+                } else if self.tok_match('=') {
+                    self.add_token(TokenType::SlashEqual)
+                } else {
+                    self.add_token(TokenType::Slash)
                 }
-            };
-            if let Some(tok) = t {
-                new_tokens.push(tok)
             }
-            self.text_buffer = String::new();
+            '"' => {
+                if self.source[self.current..].starts_with("\"\"") {
+                    self.parse_multiline_string()?
+                } else {
+                    self.parse_string()?
+                }
+            }
+            _ => {
+                if c.is_ascii_digit() {
+                    self.parse_number()?
+                } else if c == 'r' && self.is_raw_string_start() {
+                    self.parse_raw_string()?
+                } else if is_alpha(c) {
+                    self.parse_identifier()
+                } else {
+                    return Err(self.error(format!("Unexpected character: {}", c)));
+                }
+            }
+        };
+        Ok(t)
+    }
+}
+
+/// Strips the layout of a `"""` string: a newline directly after the
+/// opening quotes is dropped, a last line holding only the closing quotes'
+/// indentation is emptied, and the smallest leading indentation among the
+/// non-blank lines is removed from every line.
+fn dedent(text: &str) -> String {
+    let text = text.strip_prefix('\n').unwrap_or(text);
+    let is_blank = |l: &str| l.chars().all(|c| c == ' ' || c == '\t' || c == '\r');
+    let indent_of = |l: &str| l.chars().take_while(|c| *c == ' ' || *c == '\t').count();
+
+    let indent = text
+        .split('\n')
+        .filter(|l| !is_blank(l))
+        .map(indent_of)
+        .min()
+        .unwrap_or(0);
+
+    let lines: Vec<&str> = text
+        .split('\n')
+        .map(|l| if is_blank(l) { "" } else { &l[indent..] })
+        .collect();
+    lines.join("\n")
+}
+
+impl<'src> Iterator for Scanner<'src> {
+    type Item = Result<Token<'src>, ScanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.is_at_end() {
+            self.start = self.current;
+            if let Some(r) = self.scan_token().transpose() {
+                return Some(r);
+            }
+        }
+        if self.emitted_eof {
+            return None;
         }
-        new_tokens.push(self.add_token_base(TokenType::EOF));
-        return new_tokens;
+        self.emitted_eof = true;
+        self.start = self.current;
+        Some(Ok(self.add_token_base(TokenType::EOF)))
     }
 }
@@ -1,104 +1,116 @@
+use crate::bigint::BigInt;
+
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum TokenType {
     LeftParen,
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
     Dot,
+    DotDotDot,
     Minus,
     Plus,
     Semicolon,
     Slash,
     Star,
+    Percent,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
+    Question,
+    Colon,
 
     // One or two character tokens.
     Bang,
     BangEqual,
     Equal,
     EqualEqual,
+    Arrow,
     Greater,
     GreaterEqual,
     Less,
     LessEqual,
+    StarStar,
+    PlusEqual,
+    MinusEqual,
+    StarEqual,
+    SlashEqual,
+    PercentEqual,
+    LessLess,
+    GreaterGreater,
 
     Identifier,
     String,
     Number,
 
     And,
+    As,
+    Break,
+    Catch,
     Class,
+    Const,
+    Continue,
+    Div,
     Else,
     False,
+    Finally,
     Fun,
     For,
+    From,
+    Match,
     If,
+    Import,
+    In,
     Nil,
     Or,
     Print,
     Return,
     Super,
     This,
+    Throw,
+    Trait,
     True,
+    Try,
     Var,
     While,
+    With,
 
     EOF,
 }
-fn get_double_token_type_with_possible_equals(c: &char, o: Option<&char>) -> Option<TokenType> {
-    match o {
-        Some(x) => {
-            if *x == '=' {
-                match c {
-                    '!' => Some(TokenType::BangEqual),
-                    '=' => Some(TokenType::EqualEqual),
-                    '<' => Some(TokenType::LessEqual),
-                    '>' => Some(TokenType::GreaterEqual),
-                    _ => None, // TODO
-                }
-            } else {
-                get_double_token_type(c)
-            }
-        }
-        None => get_double_token_type(c),
-    }
-}
 
-fn get_double_token_type(c: &char) -> Option<TokenType> {
-    match c {
-        '!' => Some(TokenType::Bang),
-        '=' => Some(TokenType::Equal),
-        '<' => Some(TokenType::Less),
-        '>' => Some(TokenType::Greater),
-        _ => None, // TODO
-    }
+#[derive(Clone, Debug, PartialEq)]
+pub enum Literal {
+    Bool { lit: bool },
+    Integer { lit: i64 },
+    BigInt { lit: BigInt },
+    Float { lit: f64 },
+    String { lit: String },
+    Nil,
 }
 
 #[derive(Clone, Debug)]
-pub struct Token {
+pub struct Token<'src> {
     pub tok_type: TokenType,
-    pub lexeme: String,
+    pub lexeme: &'src str,
     pub line: i64,
-    pub bool_literal: bool,
-    pub float_literal: f64,
-    pub string_literal: String,
+    pub literal: Option<Literal>,
 }
 
-fn build_token(
-    tok_type: TokenType,
-    lexeme: String,
-    line: i64,
-    bool_literal: bool,
-    float_literal: f64,
-    string_literal: String,
-) -> Token {
-    Token {
-        tok_type,
-        lexeme,
-        line,
-        bool_literal,
-        float_literal,
-        string_literal,
+impl std::fmt::Display for Literal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Literal::Bool { lit } => write!(f, "{}", lit),
+            Literal::Integer { lit } => write!(f, "{}", lit),
+            Literal::BigInt { lit } => write!(f, "{}n", lit),
+            Literal::Float { lit } => write!(f, "{}", lit),
+            Literal::String { lit } => write!(f, "{}", lit),
+            Literal::Nil => write!(f, "Nil"),
+        }
     }
 }
 
@@ -109,42 +121,76 @@ impl std::fmt::Display for TokenType {
             TokenType::RightParen => "RightParen",
             TokenType::LeftBrace => "LeftBrace",
             TokenType::RightBrace => "RightBrace",
+            TokenType::LeftBracket => "LeftBracket",
+            TokenType::RightBracket => "RightBracket",
             TokenType::Comma => "Comma",
             TokenType::Dot => "Dot",
+            TokenType::DotDotDot => "DotDotDot",
             TokenType::Minus => "Minus",
             TokenType::Plus => "Plus",
             TokenType::Semicolon => "Semicolon",
             TokenType::Slash => "Slash",
             TokenType::Star => "Star",
+            TokenType::Percent => "Percent",
+            TokenType::Ampersand => "Ampersand",
+            TokenType::Pipe => "Pipe",
+            TokenType::Caret => "Caret",
+            TokenType::Tilde => "Tilde",
+            TokenType::Question => "Question",
+            TokenType::Colon => "Colon",
             TokenType::Bang => "Bang",
             TokenType::BangEqual => "BangEqual",
             TokenType::Equal => "Equal",
             TokenType::EqualEqual => "EqualEqual",
+            TokenType::Arrow => "Arrow",
             TokenType::Greater => "Greater",
             TokenType::GreaterEqual => "GreaterEqual",
             TokenType::Less => "Less",
             TokenType::LessEqual => "LessEqual",
+            TokenType::StarStar => "StarStar",
+            TokenType::PlusEqual => "PlusEqual",
+            TokenType::MinusEqual => "MinusEqual",
+            TokenType::StarEqual => "StarEqual",
+            TokenType::SlashEqual => "SlashEqual",
+            TokenType::PercentEqual => "PercentEqual",
+            TokenType::LessLess => "LessLess",
+            TokenType::GreaterGreater => "GreaterGreater",
 
             TokenType::Identifier => "Identifier",
             TokenType::String => "String",
             TokenType::Number => "Number",
 
             TokenType::And => "And",
+            TokenType::As => "As",
+            TokenType::Break => "Break",
+            TokenType::Catch => "Catch",
             TokenType::Class => "Class",
+            TokenType::Const => "Const",
+            TokenType::Continue => "Continue",
+            TokenType::Div => "Div",
             TokenType::Else => "Eles",
             TokenType::False => "False",
+            TokenType::Finally => "Finally",
             TokenType::Fun => "Fun",
             TokenType::For => "For",
+            TokenType::From => "From",
+            TokenType::Match => "Match",
             TokenType::If => "If",
+            TokenType::Import => "Import",
+            TokenType::In => "In",
             TokenType::Nil => "Nil",
             TokenType::Or => "Or",
             TokenType::Print => "Print",
             TokenType::Return => "Return",
             TokenType::Super => "Super",
             TokenType::This => "This",
+            TokenType::Throw => "Throw",
+            TokenType::Trait => "Trait",
             TokenType::True => "True",
+            TokenType::Try => "Try",
             TokenType::Var => "Var",
             TokenType::While => "While",
+            TokenType::With => "With",
 
             TokenType::EOF => "EOF",
         };
@@ -153,27 +199,11 @@ impl std::fmt::Display for TokenType {
     }
 }
 
-impl std::fmt::Display for Token {
+impl std::fmt::Display for Token<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let literal = match self.tok_type {
-            TokenType::Number => {
-                format!("{}", &self.float_literal)
-            }
-            TokenType::String => {
-                format!("{}", &self.string_literal)
-            }
-            TokenType::True => {
-                format!("{}", &self.bool_literal)
-            }
-            TokenType::False => {
-                format!("{}", &self.bool_literal)
-            }
-            TokenType::Nil => {
-                format!("{}", "Nil")
-            }
-            _ => {
-                format!("{}", "Other")
-            }
+        let literal = match &self.literal {
+            Some(l) => l.to_string(),
+            None => "Other".to_string(),
         };
         write!(
             f,